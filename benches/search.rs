@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use thalweg::bathymetry::Bathymetry;
+use thalweg::generator::ThalwegGenerator;
+
+// square grid of soundings about 55 m apart, with a meandering channel running from one corner to the other
+fn synthetic_inlet(size: usize) -> Vec<Bathymetry> {
+    let spacing = 0.0005;
+    let mut out = Vec::with_capacity(size * size);
+    for i in 0..size {
+        for j in 0..size {
+            let along = (i + j) as f64 / 2.0;
+            let across = i as f64 - j as f64 + 8.0 * (along / 20.0).sin();
+            let depth = 200.0 * (-(across / 10.0).powi(2)).exp() + ((i * 7 + j * 13) % 11) as f64;
+            out.push(Bathymetry::new(49.0 + i as f64 * spacing, -124.0 + j as f64 * spacing, depth));
+        }
+    }
+    out
+}
+
+fn search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("thalweg");
+    group.sample_size(10);
+    for size in [100, 200, 400] {
+        let data = synthetic_inlet(size);
+        let source = data[0].point();
+        let sink = data[data.len() - 1].point();
+        group.bench_with_input(BenchmarkId::new("build", size * size), &data, |b, data| {
+            b.iter(|| ThalwegGenerator::new(data.clone(), 80, true))
+        });
+        let generator = ThalwegGenerator::new(data, 80, true);
+        group.bench_with_input(BenchmarkId::new("search", size * size), &generator, |b, generator| {
+            b.iter(|| generator.thalweg(source, sink))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
@@ -0,0 +1,233 @@
+use crate::bathymetry::Point;
+use crate::section::Section;
+
+use json::{array, object, JsonValue};
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureKind {
+    /// Shallowest point of a rise separating deeper water on either side
+    Sill,
+    /// Deepest point of a depression
+    Basin,
+}
+
+impl fmt::Display for FeatureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureKind::Sill => write!(f, "sill"),
+            FeatureKind::Basin => write!(f, "basin"),
+        }
+    }
+}
+
+/// A sill or basin found along a section
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feature {
+    pub kind: FeatureKind,
+    /// Distance from the start of the thalweg in metres
+    pub distance: isize,
+    pub depth: f64,
+    pub point: Point,
+    /// Vertical distance in metres to the deepest sill (for a basin) or shallowest basin (for a sill)
+    /// that must be crossed to reach a more extreme point
+    pub prominence: f64,
+}
+
+/// Find sills and basins at least `min_prominence` metres high or deep, ordered along the section
+pub fn features(section: &[Section], min_prominence: f64) -> Vec<Feature> {
+    let depths: Vec<f64> = section.iter().map(Section::depth).collect();
+    let heights: Vec<f64> = depths.iter().map(|depth| -depth).collect();
+
+    let basins = peaks(&depths).into_iter().map(|p| (FeatureKind::Basin, p, prominence(&depths, p)));
+    let sills = peaks(&heights).into_iter().map(|p| (FeatureKind::Sill, p, prominence(&heights, p)));
+
+    let mut out: Vec<Feature> = basins
+        .chain(sills)
+        .filter(|&(_, _, prominence)| prominence >= min_prominence)
+        .map(|(kind, index, prominence)| Feature {
+            kind,
+            distance: section[index].distance(),
+            depth: section[index].depth(),
+            point: section[index].point(),
+            // depths are only stored to the millimetre
+            prominence: (prominence * 1000.0).round() / 1000.0,
+        })
+        .collect();
+    out.sort_by_key(|feature| feature.distance);
+    out
+}
+
+/// Indices of interior local maxima, taking the middle of any flat top
+fn peaks(values: &[f64]) -> Vec<usize> {
+    let mut out = vec![];
+    let mut i = 1;
+    while i + 1 < values.len() {
+        if values[i - 1] < values[i] {
+            let mut end = i;
+            while end + 1 < values.len() && values[end + 1] == values[i] {
+                end += 1;
+            }
+            if end + 1 < values.len() && values[end + 1] < values[i] {
+                out.push((i + end) / 2);
+            }
+            i = end + 1;
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Height of a peak above the higher of the lowest points separating it from higher ground on each side
+fn prominence(values: &[f64], peak: usize) -> f64 {
+    let height = values[peak];
+    let left = lowest_until_higher(values[..peak].iter().rev(), height);
+    let right = lowest_until_higher(values[peak + 1..].iter(), height);
+    height - f64::max(left, right)
+}
+
+fn lowest_until_higher<'a>(values: impl Iterator<Item = &'a f64>, height: f64) -> f64 {
+    values
+        .take_while(|&&value| value <= height)
+        .fold(height, |lowest, &value| f64::min(lowest, value))
+}
+
+pub fn to_csv(features: &[Feature]) -> String {
+    let mut out = String::from("kind,distance,depth,longitude,latitude,prominence\n");
+    for feature in features {
+        let (lon, lat) = feature.point;
+        out += format!(
+            "{},{},{},{},{},{}\n",
+            feature.kind,
+            feature.distance as f64 / 1000.0,
+            feature.depth,
+            lon,
+            lat,
+            feature.prominence
+        )
+        .as_str();
+    }
+    out
+}
+
+/// Point layer of features, with depth represented as negative elevation
+pub fn to_geojson(features: &[Feature]) -> String {
+    let mut collection = JsonValue::new_array();
+    for feature in features {
+        let (lon, lat) = feature.point;
+        // pushing onto an array cannot fail
+        let _ = collection.push(object! {
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": array![lon, lat, -feature.depth],
+            },
+            "properties": {
+                "kind": feature.kind.to_string(),
+                "distance": feature.distance as f64 / 1000.0,
+                "depth": feature.depth,
+                "prominence": feature.prominence,
+            },
+        });
+    }
+    object! {
+        "type": "FeatureCollection",
+        "features": collection,
+    }
+    .dump()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bathymetry::Bathymetry;
+    use crate::section;
+
+    fn profile(depths: &[f64]) -> Vec<Section> {
+        let path: Vec<Bathymetry> = depths
+            .iter()
+            .enumerate()
+            .map(|(i, &depth)| Bathymetry::new(49.0, -123.0 + i as f64 * 0.01, depth))
+            .collect();
+        section::section(&path)
+    }
+
+    #[test]
+    fn peaks_finds_interior_maxima() {
+        assert_eq!(peaks(&[1.0, 3.0, 2.0, 5.0, 4.0]), vec![1, 3]);
+        assert_eq!(peaks(&[5.0, 4.0, 3.0]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn peaks_takes_middle_of_plateau() {
+        assert_eq!(peaks(&[1.0, 3.0, 3.0, 3.0, 2.0]), vec![2]);
+        // a plateau that is only a step is not a peak
+        assert_eq!(peaks(&[1.0, 3.0, 3.0, 4.0]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn prominence_uses_higher_base() {
+        let values = [0.0, 10.0, 4.0, 8.0, 6.0, 12.0, 1.0];
+        assert_eq!(prominence(&values, 1), 6.0);
+        assert_eq!(prominence(&values, 3), 2.0);
+        assert_eq!(prominence(&values, 5), 11.0);
+    }
+
+    #[test]
+    fn finds_sill_between_basins() {
+        let section = profile(&[20.0, 150.0, 160.0, 40.0, 200.0, 180.0, 30.0]);
+        let found = features(&section, 10.0);
+        let kinds: Vec<FeatureKind> = found.iter().map(|f| f.kind).collect();
+        assert_eq!(kinds, vec![FeatureKind::Basin, FeatureKind::Sill, FeatureKind::Basin]);
+        let sill = &found[1];
+        assert_eq!(sill.depth, 40.0);
+        assert_eq!(sill.prominence, 120.0);
+        assert_eq!(sill.point, section[3].point());
+        assert_eq!(sill.distance, section[3].distance());
+        assert_eq!(found[0].prominence, 120.0);
+        assert_eq!(found[2].prominence, 170.0);
+    }
+
+    #[test]
+    fn small_features_are_ignored() {
+        let section = profile(&[20.0, 100.0, 95.0, 101.0, 30.0]);
+        let found = features(&section, 10.0);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, FeatureKind::Basin);
+        assert_eq!(found[0].depth, 101.0);
+    }
+
+    #[test]
+    fn to_csv_one_feature() {
+        let feature = Feature {
+            kind: FeatureKind::Sill,
+            distance: 4355,
+            depth: 40.0,
+            point: (-122.53, 49.24),
+            prominence: 120.0,
+        };
+        let expected = "kind,distance,depth,longitude,latitude,prominence\nsill,4.355,40,-122.53,49.24,120\n";
+        assert_eq!(to_csv(&[feature]), expected);
+    }
+
+    #[test]
+    fn to_geojson_one_feature() {
+        let feature = Feature {
+            kind: FeatureKind::Basin,
+            distance: 4355,
+            depth: 40.0,
+            point: (-122.53, 49.24),
+            prominence: 120.0,
+        };
+        let expected = concat!(
+            "{\"type\":\"FeatureCollection\",\"features\":[{",
+            "\"type\":\"Feature\",",
+            "\"geometry\":{\"type\":\"Point\",\"coordinates\":[-122.53,49.24,-40]},",
+            "\"properties\":{\"kind\":\"basin\",\"distance\":4.355,\"depth\":40,\"prominence\":120}",
+            "}]}"
+        );
+        assert_eq!(to_geojson(&[feature]), expected);
+    }
+}
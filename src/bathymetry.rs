@@ -46,6 +46,16 @@ impl Bathymetry {
         self.depth as f64 / DEPTH_PRECISION
     }
 
+    /// Rebuild a point from its stored location and depth, without rounding through degrees and metres
+    pub(crate) fn from_raw(location: Location, depth: isize) -> Self {
+        Self { location, depth }
+    }
+
+    /// Depth as stored, in millimetres
+    pub(crate) fn raw_depth(&self) -> isize {
+        self.depth
+    }
+
     pub fn distance_to(&self, other: &Bathymetry) -> f64 {
         self.distance_to_point(&other.point())
     }
@@ -4,6 +4,8 @@ use std::fs::File;
 use std::io::{BufReader, Write};
 use std::path::PathBuf;
 
+use thalweg::analysis;
+use thalweg::plot::{self, PlotOptions};
 use thalweg::read;
 use thalweg::section;
 
@@ -19,6 +21,26 @@ struct Args {
     /// Output file
     #[clap(short, long, default_value = "section.csv")]
     output: OsString,
+
+    /// Also render the section to this file, as SVG or PNG depending on extension
+    #[clap(long)]
+    plot: Option<OsString>,
+
+    /// Width of the rendered plot in pixels
+    #[clap(long, default_value_t = PlotOptions::default().width)]
+    width: u32,
+
+    /// Height of the rendered plot in pixels
+    #[clap(long, default_value_t = PlotOptions::default().height)]
+    height: u32,
+
+    /// Also write detected sills and basins to this CSV file, with a GeoJSON point layer alongside it
+    #[clap(long)]
+    features: Option<OsString>,
+
+    /// Smallest rise or depression in metres reported as a sill or basin
+    #[clap(long, default_value_t = 10.0)]
+    min_prominence: f64,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -48,5 +70,25 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut file = File::create(section_file)?;
     file.write_all(section::to_csv(&section_vec).as_bytes())?;
 
+    if let Some(plot_file) = args.plot {
+        let plot_file = PathBuf::from(plot_file);
+        let options = PlotOptions {
+            width: args.width,
+            height: args.height,
+        };
+        let contents = match plot_file.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => plot::to_png(&section_vec, &options)?,
+            _ => plot::to_svg(&section_vec, &options).into_bytes(),
+        };
+        File::create(plot_file)?.write_all(&contents)?;
+    }
+
+    if let Some(features_file) = args.features {
+        let features_file = PathBuf::from(features_file);
+        let features = analysis::features(&section_vec, args.min_prominence);
+        File::create(features_file.with_extension("csv"))?.write_all(analysis::to_csv(&features).as_bytes())?;
+        File::create(features_file.with_extension("geojson"))?.write_all(analysis::to_geojson(&features).as_bytes())?;
+    }
+
     Ok(())
 }
@@ -1,17 +1,26 @@
 use std::error::Error;
 use std::ffi::{OsStr, OsString};
-use std::fs::{self, File};
+use std::fs::File;
 use std::io::{BufReader, Write};
 use std::path::{Path, PathBuf};
+use std::thread;
 
 use thalweg::bathymetry::{Bathymetry, Point};
+use thalweg::cost::Cost;
 use thalweg::format::{self, OutputFormat};
-use thalweg::generator::ThalwegGenerator;
-use thalweg::{read, parse};
+use thalweg::generator::{Method, ThalwegGenerator, Waypoint};
+use thalweg::index::{self, Backend, PointIndex};
+use thalweg::interpolate::Interpolation;
+use thalweg::land::LandMask;
+use thalweg::read::extent::{self, Extents};
+use thalweg::read::{tiles, Positive, ReadMode, Rejected};
+use thalweg::simplify::Simplification;
+use thalweg::smooth::Smoothing;
+use thalweg::{cache, cross_section, network, read, parse, section};
 
 use clap::{Args, Parser, Subcommand};
 
-use geo::Polygon;
+use geo::MultiPolygon;
 
 /// Generate a thalweg of an inlet
 #[derive(Parser, Debug)]
@@ -28,12 +37,19 @@ enum Commands {
 
     /// Apply a path to bathymetry, effectively creating a thalweg
     FromPath(FromPathArgs),
+
+    /// Sample depth profiles perpendicular to an existing thalweg
+    CrossSection(CrossSectionArgs),
+
+    /// Generates a network of thalwegs from a mouth to several heads
+    Network(NetworkArgs),
 }
 
 // Arguments for generate
 #[derive(Args, Debug)]
 struct GenerateArgs {
-    /// Number of times to apply improvement step
+    /// Number of times to apply improvement step. Ignored with --method centre, as it moves the path onto the
+    /// deepest soundings
     #[clap(long, default_value_t = 1)]
     rounds: usize,
 
@@ -41,13 +57,44 @@ struct GenerateArgs {
     #[clap(long)]
     simplify: bool,
 
+    /// How to simplify, as `name` or `name:metres`: douglas-peucker[:metres] keeps points further than the tolerance
+    /// from the simplified line, visvalingam[:metres] removes points whose triangle with their neighbours is smaller
+    /// than the square of the tolerance
+    #[clap(long, default_value_t = Simplification::default())]
+    simplification: Simplification,
+
+    /// Smallest sill or basin in metres that simplifying must keep
+    #[clap(long, default_value_t = 10.0)]
+    min_prominence: f64,
+
+    /// Smooth the thalweg, as `name` or `name:parameter`, keeping it in the water.
+    /// One of chaikin[:iterations], catmull-rom[:samples], bspline[:samples] or window[:metres]
+    #[clap(long)]
+    smooth: Option<Smoothing>,
+
     /// Skip adding resolution to final thalweg
     #[clap(long)]
     sparse: bool,
 
-    /// Attempt a best first guess by using depths in bathymetry as weights
-    #[clap(short, long)]
-    weighted: bool,
+    /// Distance between points of the output in metres, placed exactly along the path with depths found by
+    /// --interpolation, in place of snapping a point every --resolution metres
+    #[clap(long, conflicts_with = "sparse", parse(try_from_str = parse_metres))]
+    spacing: Option<f64>,
+
+    /// Positions in the points file, counting from 1, of waypoints to leave out when no path through them exists.
+    /// The first and last points are always visited
+    #[clap(long, use_value_delimiter = true)]
+    optional: Vec<usize>,
+
+    /// Cost of moving between points in the search, as `name` or `name:parameter`.
+    /// One of uniform, distance, depth[:scale], inverse[:min_depth], exponential[:scale] or slope[:factor]
+    #[clap(short, long, default_value_t = Cost::default())]
+    cost: Cost,
+
+    /// How to find the thalweg: search for the minimum-cost path, flow as water would over gridded bathymetry,
+    /// or follow the centre of the water
+    #[clap(short, long, default_value_t = Method::default())]
+    method: Method,
 
     #[clap(flatten)]
     common: CommonArgs,
@@ -56,6 +103,50 @@ struct GenerateArgs {
 // Arguments for from-path
 #[derive(Args, Debug)]
 struct FromPathArgs {
+    /// Distance between points of the output in metres, placed exactly along the path with depths found by
+    /// --interpolation, in place of snapping a point every --resolution metres
+    #[clap(long, parse(try_from_str = parse_metres))]
+    spacing: Option<f64>,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+// Arguments for cross-section
+#[derive(Args, Debug)]
+struct CrossSectionArgs {
+    /// Distance between cross-sections along the thalweg in metres
+    #[clap(long, default_value_t = 1000.0, parse(try_from_str = parse_metres))]
+    spacing: f64,
+
+    /// Distance to sample on either side of the thalweg in metres
+    #[clap(long, default_value_t = 2000.0, parse(try_from_str = parse_metres))]
+    half_width: f64,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+// Arguments for network
+#[derive(Args, Debug)]
+struct NetworkArgs {
+    /// Skip adding resolution to the branches
+    #[clap(long)]
+    sparse: bool,
+
+    /// Distance between points of the output in metres, placed exactly along the path with depths found by
+    /// --interpolation, in place of snapping a point every --resolution metres
+    #[clap(long, conflicts_with = "sparse", parse(try_from_str = parse_metres))]
+    spacing: Option<f64>,
+
+    /// Cost of moving between points in the search, as for generate
+    #[clap(short, long, default_value_t = Cost::default())]
+    cost: Cost,
+
+    /// How to find the branches: search or flow, as for generate
+    #[clap(short, long, default_value_t = Method::default())]
+    method: Method,
+
     #[clap(flatten)]
     common: CommonArgs,
 }
@@ -66,7 +157,7 @@ struct CommonArgs {
     /// File containing the relevant points along the inlet
     points: OsString,
 
-    /// Directory containing NONNA-10 bathymetry data
+    /// Directory containing bathymetry data (NONNA-10, CSV, ESRI ASCII, XYZ or GeoTIFF)
     data: OsString,
 
     /// Directory to write resulting path to
@@ -85,6 +176,53 @@ struct CommonArgs {
     /// Bounding box to limit data used in processing
     #[clap(short, long)]
     boundingbox: Option<OsString>,
+
+    /// GeoJSON, WKT (.wkt) or shapefile (.shp) of polygons to limit data used in processing, in place of a bounding box
+    #[clap(long, conflicts_with = "boundingbox")]
+    area: Option<OsString>,
+
+    /// Fail on malformed lines in bathymetry data instead of skipping them
+    #[clap(long)]
+    strict: bool,
+
+    /// Whether the values of ESRI ASCII grids are depths (down) or elevations (up), such as from GEBCO or a DEM
+    #[clap(long, default_value_t = Positive::default())]
+    asc_positive: Positive,
+
+    /// CSV file to write skipped bathymetry lines to
+    #[clap(long)]
+    rejects: Option<OsString>,
+
+    /// GeoJSON, WKT (.wkt) or shapefile (.shp) of land polygons the thalweg may not cross
+    #[clap(long)]
+    land: Option<OsString>,
+
+    /// Number of bathymetry files to read at once, defaulting to the number of processors
+    #[clap(short, long)]
+    jobs: Option<usize>,
+
+    /// Cache of parsed and indexed bathymetry, rebuilt whenever a data file changes.
+    /// Defaults to .thwcache in the data directory. Extents of data files are recorded beside it in .thwextents
+    #[clap(long)]
+    cache: Option<OsString>,
+
+    /// Neither read nor write the bathymetry cache. Extents of data files are still recorded beside where it would be
+    #[clap(long)]
+    no_cache: bool,
+
+    /// How to store bathymetry for searching: auto, grid (regularly spaced data) or tree (scattered points)
+    #[clap(long, default_value_t = Backend::default())]
+    index: Backend,
+
+    /// How to find depths where the path is densified or laid over the bathymetry: nearest (snap to soundings),
+    /// bilinear (gridded data only), idw (inverse-distance weighting) or tin (triangulated soundings)
+    #[clap(long, default_value_t = Interpolation::default())]
+    interpolation: Interpolation,
+
+    /// Distance in metres to gather soundings from for idw and tin interpolation, defaulting to --resolution.
+    /// Raise it where soundings are further apart than the resolution
+    #[clap(long, parse(try_from_str = parse_metres))]
+    interpolation_reach: Option<f64>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -93,32 +231,34 @@ fn main() -> Result<(), Box<dyn Error>> {
     let (path, args) = match &cli.command {
         Commands::Generate(args) => {
             // points represents points of interest along the inlet
-            let bb = if let Some(bb) = &args.common.boundingbox {
-                parse::parse_bounding_box(to_utf8(bb)?)
-            } else {
-                None
-            };
-            let data = read_bathymetry_data(&args.common.data, bb)?;
             let points = read_corner_data(&args.common.points)?;
-            let generator = ThalwegGenerator::new(data, args.common.resolution, args.weighted);
-            let mut full_path = vec![];
-            for ends in points.windows(2) {
-                let source = *ends.first().expect("no source");
-                let sink = *ends.last().expect("no sink");
-                if let Some(mut path) = generator.thalweg(source, sink) {
-                    full_path.append(&mut path);
-                } else {
-                    return Err(Box::<dyn Error>::from(format!(
-                        "No path found between {:?} and {:?}",
-                        source, sink
-                    )));
+            let waypoints = waypoints(&points, &args.optional)?;
+            let bb = read_area(&args.common)?;
+            let data = read_bathymetry_data(&args.common, bb)?;
+            let generator = new_generator(&args.common, data, args.cost)?.with_method(args.method);
+            let mut full_path = generator.thalweg_via(&waypoints)?;
+            println!("path contains {} points", full_path.len());
+            if args.method == Method::Centre {
+                // sinking would drag the centreline back onto the deepest soundings
+                if args.simplify {
+                    full_path = generator.simplify(&full_path, args.simplification, args.min_prominence);
+                }
+            } else {
+                for _ in 0..args.rounds {
+                    let simplify = args.simplify.then_some(args.simplification);
+                    full_path = improve(&full_path, &generator, simplify, args.min_prominence);
                 }
             }
-            println!("path contains {} points", full_path.len());
-            for _ in 0..args.rounds {
-                full_path = improve(&full_path, &generator, args.simplify);
+            if let Some(smoothing) = args.smooth {
+                println!("Smoothing path");
+                full_path = generator.smooth(&full_path, smoothing);
             }
-            let path = if !args.sparse {
+            let path = if let Some(spacing) = args.spacing {
+                generator.resample(&full_path, spacing)?
+            } else if args.smooth.is_some() && !args.sparse {
+                // snapping to soundings would bring back the corners just smoothed away
+                generator.resample(&full_path, args.common.resolution as f64)?
+            } else if !args.sparse {
                 println!("Increasing density of path");
                 generator.populate(&full_path)
             } else {
@@ -128,16 +268,73 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         Commands::FromPath(args) => {
             // points represents a full path along the inlet
-            let bb = if let Some(bb) = &args.common.boundingbox {
-                parse::parse_bounding_box(to_utf8(bb)?)
-            } else {
-                None
+            let bb = read_area(&args.common)?;
+            let data = read_bathymetry_data(&args.common, bb)?;
+            let points = read_path_data(&args.common.points)?;
+            let generator = new_generator(&args.common, data, Cost::default())?;
+            let path = generator.from_path(&points);
+            let path = match args.spacing {
+                Some(spacing) => generator.resample(&path, spacing)?,
+                None => generator.populate(&path),
             };
-            let data = read_bathymetry_data(&args.common.data, bb)?;
+            (path, args.common.clone())
+        }
+        Commands::CrossSection(args) => {
+            // points represents the thalweg to take cross-sections of
+            let bb = read_area(&args.common)?;
+            let data = read_bathymetry_data(&args.common, bb)?;
             let points = read_path_data(&args.common.points)?;
-            let generator = ThalwegGenerator::new(data, args.common.resolution, false);
+            let generator = new_generator(&args.common, data, Cost::default())?;
             let path = generator.from_path(&points);
-            (generator.populate(&path), args.common.clone())
+            let sections = generator.cross_sections(&path, args.spacing, args.half_width)?;
+            println!("sampled {} cross-sections", sections.len());
+
+            let (contents, extension) = match args.common.format {
+                OutputFormat::GeoJson => (cross_section::to_geojson(&sections), "geojson"),
+                _ => (cross_section::to_csv(&sections), "csv"),
+            };
+            let output_file = PathBuf::from(&args.common.prefix)
+                .join("cross_sections.txt")
+                .with_extension(extension);
+            File::create(output_file)?.write_all(contents.as_bytes())?;
+            return Ok(());
+        }
+        Commands::Network(args) => {
+            // points holds the mouth followed by the heads of each branch
+            let bb = read_area(&args.common)?;
+            let data = read_bathymetry_data(&args.common, bb)?;
+            let points = read_corner_data(&args.common.points)?;
+            let (mouth, heads) = points.split_first().ok_or("no mouth given")?;
+            let generator = new_generator(&args.common, data, args.cost)?.with_method(args.method);
+            let mut branches = generator.network(*mouth, heads)?;
+            println!("network contains {} branches", branches.len());
+            if let Some(spacing) = args.spacing {
+                branches = network::densify(&branches, |path| generator.resample(path, spacing))?;
+            } else if !args.sparse {
+                println!("Increasing density of branches");
+                branches = network::densify(&branches, |path| Ok(generator.populate(path)))?;
+            }
+
+            let output_path = PathBuf::from(&args.common.prefix);
+            match args.common.format {
+                OutputFormat::GeoJson => {
+                    File::create(output_path.join("network.geojson"))?
+                        .write_all(network::to_geojson(&branches).as_bytes())?;
+                }
+                format => {
+                    for branch in &branches {
+                        let output_file = output_path
+                            .join(format!("branch_{}.txt", branch.id))
+                            .with_extension(format::extension(format));
+                        File::create(output_file)?.write_all(format::convert(format, &branch.path).as_bytes())?;
+                    }
+                }
+            }
+            for branch in &branches {
+                let section_file = output_path.join(format!("section_{}.csv", branch.id));
+                File::create(section_file)?.write_all(section::to_csv(&branch.section()).as_bytes())?;
+            }
+            return Ok(());
         }
     };
 
@@ -154,25 +351,173 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn read_bathymetry_data<T: AsRef<Path>>(dir: &T, bb: Option<Polygon<f64>>) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
-    let mut data = vec![];
-    for entry in fs::read_dir(dir)? {
-        let file_name = entry?.path();
-        let file = File::open(&file_name)?;
-        let mut reader = BufReader::new(file);
-        if let Some(ext) = file_name.extension() {
-            match ext.to_str() {
-                Some("txt") => data.extend(read::bathymetry::from_nonna(&mut reader, &bb)?),
-                Some("csv") => data.extend(read::bathymetry::from_csv(&mut reader, &bb)?),
-                Some(..) => data.extend(read::bathymetry::from_nonna(&mut reader, &bb)?),
-                None => data.extend(read::bathymetry::from_nonna(&mut reader, &bb)?),
+fn read_bathymetry_data(args: &CommonArgs, bb: Option<MultiPolygon<f64>>) -> Result<Box<dyn PointIndex>, thalweg::Error> {
+    let mode = if args.strict { ReadMode::Strict } else { ReadMode::Lenient };
+    let paths = tiles::list(&args.data)?;
+    let cache_file = match &args.cache {
+        Some(file) => PathBuf::from(file),
+        None => PathBuf::from(&args.data).join(cache::DEFAULT_NAME),
+    };
+    let key = cache::Key::new(&paths, &bb, mode, args.asc_positive, args.index)?;
+    let cached = if args.no_cache {
+        None
+    } else {
+        // a damaged cache is rebuilt like a stale one
+        cache::load_file(&cache_file, &key).unwrap_or_else(|e| {
+            eprintln!("ignoring cache {}: {}", cache_file.display(), e);
+            None
+        })
+    };
+
+    let (points, rejected) = match cached {
+        Some(cached) => {
+            eprintln!("loaded {} points from {}", cached.points.len(), cache_file.display());
+            (cached.points, cached.rejected)
+        }
+        None => {
+            let mut data = vec![];
+            let mut rejected = vec![];
+            // extents are kept beside the cache, but learned and used whether or not the cache is
+            let sidecar = cache_file.with_file_name(extent::SIDECAR_NAME);
+            for tile in read_tiles(args, &paths, &bb, mode, &sidecar)? {
+                data.extend(tile.parsed.data.into_iter().filter(|bath| bath.depth() > 0.0));
+                rejected.extend(tile.parsed.rejected.into_iter().map(|r| (tile.path.clone(), r)));
+            }
+            if data.is_empty() {
+                return Err(thalweg::Error::EmptyDataset);
+            }
+            let points = index::build(data, args.index)?;
+            if !args.no_cache {
+                // the cache only saves time, so failing to write one is not fatal
+                if let Err(e) = cache::save_file(&cache_file, &key, points.as_ref(), &rejected) {
+                    eprintln!("could not write cache {}: {}", cache_file.display(), e);
+                }
             }
+            (points, rejected)
+        }
+    };
+
+    if !rejected.is_empty() {
+        println!("skipped {} malformed lines", rejected.len());
+    }
+    if let Some(rejects) = &args.rejects {
+        write_rejects(rejects, &rejected)?;
+    }
+    Ok(points)
+}
+
+fn read_tiles(
+    args: &CommonArgs,
+    paths: &[PathBuf],
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+    sidecar: &Path,
+) -> Result<Vec<tiles::Tile>, thalweg::Error> {
+    let workers = match args.jobs {
+        Some(jobs) => jobs,
+        None => thread::available_parallelism().map_or(1, usize::from),
+    };
+    // extents only save time, so unreadable ones are learned again like missing ones
+    let mut extents = Extents::load(sidecar).unwrap_or_else(|e| {
+        eprintln!("ignoring {}: {}", sidecar.display(), e);
+        Extents::default()
+    });
+    let loaded = tiles::read_all(paths, bb, mode, args.asc_positive, &extents, workers, |progress| {
+        eprint!(
+            "\rloaded {}/{} files ({} outside the bounding box), {} points",
+            progress.files, progress.total, progress.skipped, progress.points
+        );
+    });
+    if !paths.is_empty() {
+        eprintln!();
+    }
+    let loaded = loaded?;
+    for tile in &loaded {
+        if let Some(extent) = tile.extent {
+            extents.insert(tile.path.clone(), extent)?;
         }
     }
-    Ok(data.into_iter().filter(|bath| bath.depth() > 0.0).collect())
+    // extents only save time, so failing to record them is not fatal
+    if let Err(e) = extents.save(sidecar) {
+        eprintln!("could not write {}: {}", sidecar.display(), e);
+    }
+    Ok(loaded)
+}
+
+fn new_generator(args: &CommonArgs, points: Box<dyn PointIndex>, cost: Cost) -> Result<ThalwegGenerator, thalweg::Error> {
+    let generator = ThalwegGenerator::from_index(points, args.resolution, false);
+    let cost = cost.build(generator.max_depth());
+    let mut generator = generator.with_cost_function(cost).with_interpolation(args.interpolation)?;
+    if let Some(reach) = args.interpolation_reach {
+        generator = generator.with_interpolation_reach(reach);
+    }
+    if let Some(file) = &args.land {
+        let land = read_polygons(file)?;
+        Ok(generator.with_land_mask(LandMask::new(land)))
+    } else {
+        Ok(generator)
+    }
 }
 
-fn read_corner_data<T: AsRef<Path>>(file: &T) -> Result<Vec<Point>, Box<dyn Error>> {
+fn read_area(args: &CommonArgs) -> Result<Option<MultiPolygon<f64>>, Box<dyn Error>> {
+    if let Some(file) = &args.area {
+        Ok(Some(read_polygons(file)?))
+    } else if let Some(bb) = &args.boundingbox {
+        Ok(parse::parse_bounding_box(to_utf8(bb)?).map(MultiPolygon::from))
+    } else {
+        Ok(None)
+    }
+}
+
+fn read_polygons<T: AsRef<Path>>(file: &T) -> Result<MultiPolygon<f64>, thalweg::Error> {
+    let polygons = File::open(file)?;
+    let mut reader = BufReader::new(polygons);
+    let polygons = match file.as_ref().extension().and_then(|ext| ext.to_str()) {
+        Some("wkt") => read::polygon::from_wkt(&mut reader),
+        Some("shp") => read::polygon::from_shapefile(&mut reader),
+        _ => read::polygon::from_geojson(&mut reader),
+    };
+    polygons.map_err(|e| e.with_file(file))
+}
+
+fn write_rejects<T: AsRef<Path>>(file: T, rejected: &[(PathBuf, Rejected)]) -> Result<(), thalweg::Error> {
+    let mut out = String::from("file,line,column,reason,text\n");
+    for (file_name, reject) in rejected {
+        out += format!(
+            "\"{}\",{},{},\"{}\",\"{}\"\n",
+            file_name.display().to_string().replace('"', "\"\""),
+            reject.line,
+            reject.column,
+            reject.reason.replace('"', "\"\""),
+            reject.text.replace('"', "\"\""),
+        )
+        .as_str();
+    }
+    File::create(file)?.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Points to visit in turn, those at the 1-based positions in `optional` being optional
+fn waypoints(points: &[Point], optional: &[usize]) -> Result<Vec<Waypoint>, thalweg::Error> {
+    // the first and last points are always visited, so only those between them can be optional
+    if let Some(&position) = optional.iter().find(|&&position| position < 2 || position >= points.len()) {
+        return Err(thalweg::Error::InvalidInput(format!(
+            "--optional {} is not a position between the first and last of the {} points",
+            position,
+            points.len()
+        )));
+    }
+    Ok(points
+        .iter()
+        .enumerate()
+        .map(|(index, &point)| Waypoint {
+            point,
+            optional: optional.contains(&(index + 1)),
+        })
+        .collect())
+}
+
+fn read_corner_data<T: AsRef<Path>>(file: &T) -> Result<Vec<Point>, thalweg::Error> {
     let points = File::open(file)?;
     let mut reader = BufReader::new(points);
     if let Some(ext) = file.as_ref().extension() {
@@ -188,7 +533,7 @@ fn read_corner_data<T: AsRef<Path>>(file: &T) -> Result<Vec<Point>, Box<dyn Erro
     }
 }
 
-fn read_path_data<T: AsRef<Path>>(file: &T) -> Result<Vec<Point>, Box<dyn Error>> {
+fn read_path_data<T: AsRef<Path>>(file: &T) -> Result<Vec<Point>, thalweg::Error> {
     let points = File::open(file)?;
     let mut reader = BufReader::new(points);
     if let Some(ext) = file.as_ref().extension() {
@@ -204,16 +549,20 @@ fn read_path_data<T: AsRef<Path>>(file: &T) -> Result<Vec<Point>, Box<dyn Error>
     }
 }
 
-fn improve(path: &[Bathymetry], generator: &ThalwegGenerator, simplify: bool) -> Vec<Bathymetry> {
+fn improve(
+    path: &[Bathymetry],
+    generator: &ThalwegGenerator,
+    simplify: Option<Simplification>,
+    min_prominence: f64,
+) -> Vec<Bathymetry> {
     let mut current_path = generator.add_midpoints(path);
     loop {
         // find fixed-point thalweg - mostly in an attempt to ensure the thalweg does not pass over land
         let new_path = generator.sink(&current_path);
         if new_path == current_path {
-            break if simplify {
-                generator.simplify(&current_path)
-            } else {
-                current_path
+            break match simplify {
+                Some(simplification) => generator.simplify(&current_path, simplification, min_prominence),
+                None => current_path,
             };
         }
         // combine points that are too close and may produce strange paths on further sink steps
@@ -221,6 +570,14 @@ fn improve(path: &[Bathymetry], generator: &ThalwegGenerator, simplify: bool) ->
     }
 }
 
+fn parse_metres(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(metres) if metres.is_finite() && metres > 0.0 => Ok(metres),
+        Ok(_) => Err("must be a positive number of metres".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
 fn to_utf8(input: &OsStr) -> Result<&str, Box::<dyn Error>> {
     input.to_str().ok_or_else(|| Box::<dyn Error>::from(format!("{:?} is not valid utf-8", input)))
 }
@@ -0,0 +1,420 @@
+use crate::bathymetry::Bathymetry;
+use crate::error::Error;
+use crate::index::{self, Backend, GridIndex, Layout, PointIndex, TreeIndex};
+use crate::read::{Positive, ReadMode, Rejected};
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use geo::MultiPolygon;
+
+/// Extension of cache files, which are never read as bathymetry
+pub const EXTENSION: &str = "thwcache";
+
+/// Name of the cache kept in a data directory when no other is given
+pub const DEFAULT_NAME: &str = ".thwcache";
+
+const MAGIC: &[u8; 8] = b"THWCACHE";
+// raised whenever the contents of a cache change, including which layout `auto` picks
+const VERSION: u32 = 2;
+
+const SCATTERED: u8 = 0;
+const GRID: u8 = 1;
+
+/// Everything a cache depends on: the source files as they were on disk, and how they were read and indexed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    /// Identify `paths` by name, size and modification time, along with the settings used to read them
+    pub fn new(
+        paths: &[PathBuf],
+        bb: &Option<MultiPolygon<f64>>,
+        mode: ReadMode,
+        positive: Positive,
+        backend: Backend,
+    ) -> Result<Self, Error> {
+        let mut out = vec![];
+        put_u64(&mut out, paths.len() as u64);
+        for path in paths {
+            let stamp = Stamp::of(path)?;
+            put_str(&mut out, &path.to_string_lossy());
+            put_u64(&mut out, stamp.size);
+            put_u64(&mut out, stamp.seconds);
+            put_u64(&mut out, stamp.nanos as u64);
+        }
+        match bb {
+            Some(area) => {
+                put_u64(&mut out, area.0.len() as u64);
+                for polygon in &area.0 {
+                    put_u64(&mut out, polygon.interiors().len() as u64);
+                    for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors()) {
+                        put_u64(&mut out, ring.0.len() as u64);
+                        for coord in &ring.0 {
+                            put_f64(&mut out, coord.x);
+                            put_f64(&mut out, coord.y);
+                        }
+                    }
+                }
+            }
+            None => put_u64(&mut out, 0),
+        }
+        out.push(match mode {
+            ReadMode::Strict => 0,
+            ReadMode::Lenient => 1,
+        });
+        out.push(match positive {
+            Positive::Down => 0,
+            Positive::Up => 1,
+        });
+        out.push(match backend {
+            Backend::Auto => 0,
+            Backend::Grid => 1,
+            Backend::Tree => 2,
+        });
+        Ok(Self(out))
+    }
+}
+
+/// Size and modification time of a file, which change whenever it is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Stamp {
+    pub(crate) size: u64,
+    pub(crate) seconds: u64,
+    pub(crate) nanos: u32,
+}
+
+impl Stamp {
+    pub(crate) fn of(path: &Path) -> Result<Self, Error> {
+        let metadata = fs::metadata(path)?;
+        // files with times before the epoch are unusual enough to always look the same
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            size: metadata.len(),
+            seconds: modified.as_secs(),
+            nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// Indexed bathymetry and the lines skipped while reading it, as loaded from a cache
+pub struct Cached {
+    pub points: Box<dyn PointIndex>,
+    pub rejected: Vec<(PathBuf, Rejected)>,
+}
+
+/// Write indexed bathymetry and the lines skipped while reading it
+pub fn save<W: Write>(
+    output: &mut W,
+    key: &Key,
+    points: &dyn PointIndex,
+    rejected: &[(PathBuf, Rejected)],
+) -> Result<(), Error> {
+    let mut out = vec![];
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    put_u64(&mut out, key.0.len() as u64);
+    out.extend(&key.0);
+
+    put_u64(&mut out, points.len() as u64);
+    for node in points.nodes() {
+        let (longitude, latitude) = node.location();
+        put_i64(&mut out, longitude as i64);
+        put_i64(&mut out, latitude as i64);
+        put_i64(&mut out, node.raw_depth() as i64);
+    }
+
+    put_u64(&mut out, rejected.len() as u64);
+    for (path, reject) in rejected {
+        put_str(&mut out, &path.to_string_lossy());
+        put_u64(&mut out, reject.line as u64);
+        put_u64(&mut out, reject.column as u64);
+        put_str(&mut out, &reject.text);
+        put_str(&mut out, &reject.reason);
+    }
+
+    match points.layout() {
+        Layout::Scattered => out.push(SCATTERED),
+        Layout::Grid(grid) => {
+            out.push(GRID);
+            for axis in [grid.columns(), grid.rows()] {
+                put_f64(&mut out, axis.origin);
+                put_f64(&mut out, axis.step);
+                put_u64(&mut out, axis.count as u64);
+            }
+            for &id in grid.cells() {
+                put_u64(&mut out, id as u64);
+            }
+        }
+    }
+
+    output.write_all(&out)?;
+    Ok(())
+}
+
+/// Read a cache written by `save`, or nothing if it was written for a different key.
+/// Grids are restored as they were saved, while trees are bulk loaded again from the points
+pub fn load<R: Read>(input: &mut R, key: &Key) -> Result<Option<Cached>, Error> {
+    let mut buffer = vec![];
+    input.read_to_end(&mut buffer)?;
+    let mut decoder = Decoder { buffer: &buffer, at: 0 };
+
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(Error::InvalidInput("Not a thalweg cache".to_string()));
+    }
+    let version = u32::from_le_bytes(decoder.take(4)?.try_into().expect("four bytes"));
+    let key_length = decoder.usize()?;
+    if version != VERSION || decoder.take(key_length)? != key.0.as_slice() {
+        return Ok(None);
+    }
+
+    let count = decoder.usize()?;
+    let mut nodes = Vec::with_capacity(count.min(buffer.len() / 24));
+    for _ in 0..count {
+        let longitude = decoder.i64()? as isize;
+        let latitude = decoder.i64()? as isize;
+        let depth = decoder.i64()? as isize;
+        nodes.push(Bathymetry::from_raw((longitude, latitude), depth));
+    }
+
+    let count = decoder.usize()?;
+    let mut rejected = vec![];
+    for _ in 0..count {
+        let path = PathBuf::from(decoder.string()?);
+        let line = decoder.usize()?;
+        let column = decoder.usize()?;
+        let text = decoder.string()?;
+        let reason = decoder.string()?;
+        rejected.push((
+            path,
+            Rejected {
+                line,
+                column,
+                text,
+                reason,
+            },
+        ));
+    }
+
+    let points: Box<dyn PointIndex> = match decoder.take(1)?[0] {
+        SCATTERED => Box::new(TreeIndex::new(nodes)),
+        GRID => {
+            let columns = decoder.axis()?;
+            let rows = decoder.axis()?;
+            let size = columns.count.saturating_mul(rows.count);
+            let mut cells = Vec::with_capacity(size.min(buffer.len() / 8));
+            for _ in 0..size {
+                cells.push(decoder.usize()?);
+            }
+            let grid = GridIndex::from_parts(nodes, columns, rows, cells)
+                .ok_or_else(|| Error::InvalidInput("Inconsistent grid in cache".to_string()))?;
+            Box::new(grid)
+        }
+        other => return Err(Error::InvalidInput(format!("Unknown index {} in cache", other))),
+    };
+    Ok(Some(Cached { points, rejected }))
+}
+
+/// Whether a file is a cache, or one being written
+pub fn is_cache_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.strip_suffix(".partial").unwrap_or(name))
+        .is_some_and(|name| name.ends_with(&format!(".{}", EXTENSION)))
+}
+
+/// Load a cache file if it exists and matches `key`
+pub fn load_file<P: AsRef<Path>>(path: P, key: &Key) -> Result<Option<Cached>, Error> {
+    match fs::File::open(path) {
+        Ok(mut file) => load(&mut file, key),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write a cache file, replacing any previous one only once it is complete
+pub fn save_file<P: AsRef<Path>>(
+    path: P,
+    key: &Key,
+    points: &dyn PointIndex,
+    rejected: &[(PathBuf, Rejected)],
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    save(&mut fs::File::create(&partial)?, key, points, rejected)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend(value.to_le_bytes());
+}
+
+fn put_i64(out: &mut Vec<u8>, value: i64) {
+    out.extend(value.to_le_bytes());
+}
+
+fn put_f64(out: &mut Vec<u8>, value: f64) {
+    out.extend(value.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u64(out, value.len() as u64);
+    out.extend(value.as_bytes());
+}
+
+struct Decoder<'a> {
+    buffer: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .at
+            .checked_add(length)
+            .filter(|&end| end <= self.buffer.len())
+            .ok_or_else(|| Error::InvalidInput("Truncated cache file".to_string()))?;
+        let out = &self.buffer[self.at..end];
+        self.at = end;
+        Ok(out)
+    }
+
+    fn eight(&mut self) -> Result<[u8; 8], Error> {
+        Ok(self.take(8)?.try_into().expect("eight bytes"))
+    }
+
+    fn usize(&mut self) -> Result<usize, Error> {
+        let value = u64::from_le_bytes(self.eight()?);
+        usize::try_from(value).map_err(|_| Error::InvalidInput("Oversized value in cache".to_string()))
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_le_bytes(self.eight()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_le_bytes(self.eight()?))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let length = self.usize()?;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| Error::InvalidInput("Invalid text in cache".to_string()))
+    }
+
+    fn axis(&mut self) -> Result<index::Axis, Error> {
+        Ok(index::Axis {
+            origin: self.f64()?,
+            step: self.f64()?,
+            count: self.usize()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> Key {
+        Key(name.as_bytes().to_vec())
+    }
+
+    fn grid() -> Vec<Bathymetry> {
+        let mut out = vec![];
+        for i in 0..10 {
+            for j in 0..10 {
+                if (i + j) % 4 != 0 {
+                    out.push(Bathymetry::new(49.0 + i as f64 * 0.0007, -123.0 + j as f64 * 0.0011, 1.234 + i as f64));
+                }
+            }
+        }
+        out
+    }
+
+    fn round_trip(points: Box<dyn PointIndex>, rejected: &[(PathBuf, Rejected)]) -> Cached {
+        let mut buffer = vec![];
+        save(&mut buffer, &key("a"), points.as_ref(), rejected).unwrap();
+        load(&mut buffer.as_slice(), &key("a")).unwrap().unwrap()
+    }
+
+    #[test]
+    fn grid_round_trips() {
+        let points = index::build(grid(), Backend::Grid).unwrap();
+        let loaded = round_trip(index::build(grid(), Backend::Grid).unwrap(), &[]);
+        assert_eq!(loaded.points.nodes(), points.nodes());
+        match (loaded.points.layout(), points.layout()) {
+            (Layout::Grid(loaded), Layout::Grid(original)) => {
+                assert_eq!(loaded.columns(), original.columns());
+                assert_eq!(loaded.rows(), original.rows());
+                assert_eq!(loaded.cells(), original.cells());
+            }
+            _ => panic!("grid not restored"),
+        }
+    }
+
+    #[test]
+    fn scattered_points_and_rejects_round_trip() {
+        let nodes = vec![Bathymetry::new(49.1, -123.2, 10.5), Bathymetry::new(49.3, -123.25, 0.001)];
+        let rejected = vec![(
+            PathBuf::from("data/tile.txt"),
+            Rejected {
+                line: 3,
+                column: 2,
+                text: "49-00-00N bad".to_string(),
+                reason: "Invalid longitude".to_string(),
+            },
+        )];
+        let loaded = round_trip(index::build(nodes.clone(), Backend::Tree).unwrap(), &rejected);
+        assert_eq!(loaded.points.nodes(), nodes.as_slice());
+        assert!(matches!(loaded.points.layout(), Layout::Scattered));
+        assert_eq!(loaded.points.nearest(&(-123.25, 49.29)), Some(1));
+        assert_eq!(loaded.rejected, rejected);
+    }
+
+    #[test]
+    fn other_key_is_a_miss() {
+        let mut buffer = vec![];
+        save(&mut buffer, &key("a"), index::auto(grid()).as_ref(), &[]).unwrap();
+        assert!(load(&mut buffer.as_slice(), &key("b")).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_cache_is_an_error() {
+        let mut buffer = vec![];
+        save(&mut buffer, &key("a"), index::auto(grid()).as_ref(), &[]).unwrap();
+        buffer.truncate(buffer.len() - 3);
+        assert!(matches!(load(&mut buffer.as_slice(), &key("a")), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn cache_files_are_matched_by_extension() {
+        for name in [".thwcache", "survey.thwcache", ".thwcache.partial", "survey.thwcache.partial"] {
+            assert!(is_cache_file(Path::new(name)), "{}", name);
+        }
+        for name in ["survey.thwcache.csv", "survey.csv", "thwcache.csv", "survey.partial"] {
+            assert!(!is_cache_file(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn key_changes_with_file_contents() {
+        let path = std::env::temp_dir().join("thalweg-key-changes-with-file-contents.txt");
+        fs::write(&path, "one").unwrap();
+        let paths = vec![path.clone()];
+        let key = |mode, positive| Key::new(&paths, &None, mode, positive, Backend::Auto).unwrap();
+        let before = key(ReadMode::Lenient, Positive::Down);
+        assert_eq!(before, key(ReadMode::Lenient, Positive::Down));
+        assert_ne!(before, key(ReadMode::Strict, Positive::Down));
+        assert_ne!(before, key(ReadMode::Lenient, Positive::Up));
+        fs::write(&path, "three").unwrap();
+        assert_ne!(before, key(ReadMode::Lenient, Positive::Down));
+        fs::remove_file(path).unwrap();
+    }
+}
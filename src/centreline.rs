@@ -0,0 +1,245 @@
+use crate::bathymetry::Point;
+use crate::generator::Priority;
+use crate::index::{PointIndex, EARTH_RADIUS};
+use crate::land::LandMask;
+use crate::read::extent;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// lattices are coarsened to keep them to about this many cells
+const MAX_CELLS: f64 = 1000000.0;
+
+// points sampled to estimate how far apart the soundings are
+const SAMPLES: usize = 1000;
+
+/// Line along the middle of the water between `source` and `sink`, approximating the medial axis
+///
+/// The water is taken to be wherever a sounding is close by, less any land, rasterised onto a lattice with the
+/// typical spacing of the soundings. The line is the path across the lattice that keeps furthest from
+/// the banks, each step costing its length divided by the square of its distance from the nearest dry cell.
+pub fn centreline(points: &dyn PointIndex, land: Option<&LandMask>, source: Point, sink: Point) -> Option<Vec<Point>> {
+    let lattice = Lattice::new(points, land, spacing(points)?)?;
+    lattice.path(source, sink)
+}
+
+/// Median distance from a sample of the points to the nearest other point
+pub(crate) fn spacing(points: &dyn PointIndex) -> Option<f64> {
+    let nodes = points.nodes();
+    let stride = (nodes.len() / SAMPLES).max(1);
+    let mut distances: Vec<f64> = nodes
+        .iter()
+        .step_by(stride)
+        .filter_map(|node| {
+            let other = points.nearest_where(&node.point(), &|other| other.location() != node.location())?;
+            Some(node.distance_to(points.get(other)))
+        })
+        .collect();
+    distances.sort_by(f64::total_cmp);
+    distances.get(distances.len() / 2).copied()
+}
+
+/// Regular latitude-longitude lattice over the points, holding the distance from each wet cell to the nearest dry one
+struct Lattice {
+    west: f64,
+    south: f64,
+    step_lon: f64,
+    step_lat: f64,
+    width: usize,
+    height: usize,
+    /// Distance to the nearest dry cell in metres, zero for dry cells
+    clearance: Vec<f64>,
+}
+
+impl Lattice {
+    fn new(points: &dyn PointIndex, land: Option<&LandMask>, spacing: f64) -> Option<Self> {
+        let envelope = extent::envelope(points.nodes())?;
+        let (min, max) = (envelope.min(), envelope.max());
+        let mut step_lat = (spacing / EARTH_RADIUS).to_degrees();
+        let mut step_lon = step_lat / ((min.y + max.y) / 2.0).to_radians().cos();
+        let cells = ((max.x - min.x) / step_lon + 7.0) * ((max.y - min.y) / step_lat + 7.0);
+        if cells > MAX_CELLS {
+            let scale = (cells / MAX_CELLS).sqrt();
+            step_lat *= scale;
+            step_lon *= scale;
+        }
+        // a margin all round wide enough to hold the edge of the water, and dry at its outer edge
+        let margin = 3.0;
+        let mut lattice = Self {
+            west: min.x - margin * step_lon,
+            south: min.y - margin * step_lat,
+            step_lon,
+            step_lat,
+            width: ((max.x - min.x) / step_lon).ceil() as usize + 7,
+            height: ((max.y - min.y) / step_lat).ceil() as usize + 7,
+            clearance: vec![],
+        };
+
+        // far enough to reach a sounding from anywhere between rows spaced further apart than the soundings along
+        // them; widening the water this way moves both banks out alike, leaving the middle where it was
+        let reach = 1.5 * step_lat.to_radians() * EARTH_RADIUS;
+        let wet: Vec<bool> = (0..lattice.width * lattice.height)
+            .map(|cell| {
+                let (column, row) = (cell % lattice.width, cell / lattice.width);
+                let centre = lattice.centre(cell);
+                column > 0
+                    && row > 0
+                    && column < lattice.width - 1
+                    && row < lattice.height - 1
+                    && !land.is_some_and(|land| land.contains(centre))
+                    && points.within(&centre, reach).next().is_some()
+            })
+            .collect();
+        lattice.clearance = lattice.distance_from_banks(&wet);
+        Some(lattice)
+    }
+
+    fn centre(&self, cell: usize) -> Point {
+        let (column, row) = (cell % self.width, cell / self.width);
+        (
+            self.west + column as f64 * self.step_lon,
+            self.south + row as f64 * self.step_lat,
+        )
+    }
+
+    /// Neighbouring cells of the eight around `cell`, with the distance to each in metres
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let (column, row) = ((cell % self.width) as isize, (cell / self.width) as isize);
+        let latitude = self.centre(cell).1;
+        let dx = self.step_lon.to_radians() * EARTH_RADIUS * latitude.to_radians().cos();
+        let dy = self.step_lat.to_radians() * EARTH_RADIUS;
+        (-1..=1)
+            .flat_map(move |dr| (-1..=1).map(move |dc| (dc, dr)))
+            .filter(|&offset| offset != (0, 0))
+            .filter_map(move |(dc, dr)| {
+                let (c, r) = (column + dc, row + dr);
+                let inside = c >= 0 && r >= 0 && (c as usize) < self.width && (r as usize) < self.height;
+                let length = f64::hypot(dc as f64 * dx, dr as f64 * dy);
+                inside.then(|| (r as usize * self.width + c as usize, length))
+            })
+    }
+
+    /// Distance from each cell to the nearest dry cell, measured along steps between neighbouring cells
+    fn distance_from_banks(&self, wet: &[bool]) -> Vec<f64> {
+        let mut distance = vec![f64::INFINITY; wet.len()];
+        let mut queue = BinaryHeap::new();
+        for (cell, _) in wet.iter().enumerate().filter(|&(_, &wet)| !wet) {
+            distance[cell] = 0.0;
+            queue.push(Reverse((Priority(0.0), cell)));
+        }
+        while let Some(Reverse((Priority(here), cell))) = queue.pop() {
+            if here > distance[cell] {
+                continue;
+            }
+            for (neighbour, length) in self.neighbours(cell) {
+                if here + length < distance[neighbour] {
+                    distance[neighbour] = here + length;
+                    queue.push(Reverse((Priority(here + length), neighbour)));
+                }
+            }
+        }
+        distance
+    }
+
+    /// Wet cell closest to a point
+    fn nearest_wet(&self, (longitude, latitude): Point) -> Option<usize> {
+        let scale = latitude.to_radians().cos();
+        (0..self.clearance.len())
+            .filter(|&cell| self.clearance[cell] > 0.0)
+            .min_by(|&a, &b| {
+                let offset = |cell| {
+                    let (x, y) = self.centre(cell);
+                    ((x - longitude) * scale).powi(2) + (y - latitude).powi(2)
+                };
+                offset(a).total_cmp(&offset(b))
+            })
+    }
+
+    /// Path across wet cells from `source` to `sink` keeping as far from the banks as it can
+    fn path(&self, source: Point, sink: Point) -> Option<Vec<Point>> {
+        let start = self.nearest_wet(source)?;
+        let end = self.nearest_wet(sink)?;
+        let mut cost = vec![f64::INFINITY; self.clearance.len()];
+        let mut previous = vec![usize::MAX; self.clearance.len()];
+        let mut queue = BinaryHeap::new();
+        cost[start] = 0.0;
+        queue.push(Reverse((Priority(0.0), start)));
+        while let Some(Reverse((Priority(here), cell))) = queue.pop() {
+            if cell == end {
+                break;
+            }
+            if here > cost[cell] {
+                continue;
+            }
+            for (neighbour, length) in self.neighbours(cell) {
+                if self.clearance[neighbour] == 0.0 {
+                    continue;
+                }
+                let clearance = (self.clearance[cell] + self.clearance[neighbour]) / 2.0;
+                let through = here + length / (clearance * clearance);
+                if through < cost[neighbour] {
+                    cost[neighbour] = through;
+                    previous[neighbour] = cell;
+                    queue.push(Reverse((Priority(through), neighbour)));
+                }
+            }
+        }
+        if end != start && previous[end] == usize::MAX {
+            return None;
+        }
+
+        let mut out = vec![self.centre(end)];
+        let mut cell = end;
+        while cell != start {
+            cell = previous[cell];
+            out.push(self.centre(cell));
+        }
+        out.reverse();
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bathymetry::Bathymetry;
+    use crate::index::TreeIndex;
+
+    use geo::{LineString, MultiPolygon, Polygon};
+
+    // channel running east along the equator, eleven soundings wide, deepest off to one side
+    fn channel() -> TreeIndex {
+        let mut points = vec![];
+        for x in 0..=40 {
+            for y in 0..=10 {
+                let depth = if y == 2 + x % 3 { 30.0 } else { 10.0 };
+                points.push(Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, depth));
+            }
+        }
+        TreeIndex::new(points)
+    }
+
+    #[test]
+    fn centreline_keeps_to_the_middle_of_the_channel() {
+        let line = centreline(&channel(), None, (0.0, 0.002), (0.04, 0.008)).unwrap();
+        assert!(line.first().unwrap().0 < 0.002 && line.last().unwrap().0 > 0.038);
+        // well away from the ends, the line runs along the middle sounding
+        for &(x, y) in line.iter().filter(|(x, _)| (0.01..=0.03).contains(x)) {
+            assert!((y - 0.005).abs() < 0.0006, "({}, {}) is off centre", x, y);
+        }
+    }
+
+    #[test]
+    fn centreline_keeps_off_land() {
+        // land covering the northern half of the channel
+        let land = Polygon::new(
+            LineString::from(vec![(-1.0, 0.0055), (1.0, 0.0055), (1.0, 1.0), (-1.0, 1.0), (-1.0, 0.0055)]),
+            vec![],
+        );
+        let land = LandMask::new(MultiPolygon(vec![land]));
+        let line = centreline(&channel(), Some(&land), (0.0, 0.002), (0.04, 0.002)).unwrap();
+        for &(x, y) in line.iter().filter(|(x, _)| (0.01..=0.03).contains(x)) {
+            assert!((y - 0.0025).abs() < 0.0011, "({}, {}) is off centre", x, y);
+        }
+    }
+}
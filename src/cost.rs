@@ -0,0 +1,284 @@
+use crate::bathymetry::Bathymetry;
+
+use std::fmt;
+use std::str;
+
+/// Cost of moving along an edge of the search graph
+pub trait CostFunction {
+    /// `length` is the distance between the two points in metres
+    fn cost(&self, current: &Bathymetry, neighbor: &Bathymetry, length: f64) -> f64;
+
+    /// Lower bound on `cost / length` over every edge, which keeps the search heuristic admissible.
+    /// Zero is always safe, at the expense of exploring more of the graph
+    fn min_cost_per_metre(&self) -> f64 {
+        0.0
+    }
+}
+
+/// Every edge costs the same whatever its length, giving the path with the fewest points
+///
+/// As edges may be of any length, nothing above zero bounds the cost per metre, so the search gets no help from
+/// its heuristic and explores as Dijkstra's algorithm would
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Uniform;
+
+impl CostFunction for Uniform {
+    fn cost(&self, _: &Bathymetry, _: &Bathymetry, _: f64) -> f64 {
+        1.0
+    }
+}
+
+/// Shortest geographic path, ignoring depth
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Distance;
+
+impl CostFunction for Distance {
+    fn cost(&self, _: &Bathymetry, _: &Bathymetry, length: f64) -> f64 {
+        length
+    }
+
+    fn min_cost_per_metre(&self) -> f64 {
+        1.0
+    }
+}
+
+/// Edge length scaled by how much shallower the neighbour is than the deepest point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthRelative {
+    pub max_depth: f64,
+    pub scale: f64,
+}
+
+impl CostFunction for DepthRelative {
+    fn cost(&self, _: &Bathymetry, neighbor: &Bathymetry, length: f64) -> f64 {
+        length * (self.max_depth - neighbor.depth() + self.scale) / self.scale
+    }
+
+    fn min_cost_per_metre(&self) -> f64 {
+        // reached by neighbours at the maximum depth
+        1.0
+    }
+}
+
+/// Edge length divided by the depth of the neighbour, so deep water is cheap to cross
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InverseDepth {
+    pub max_depth: f64,
+    /// Depths shallower than this are treated as this depth, avoiding division by zero
+    pub min_depth: f64,
+}
+
+impl CostFunction for InverseDepth {
+    fn cost(&self, _: &Bathymetry, neighbor: &Bathymetry, length: f64) -> f64 {
+        length / neighbor.depth().max(self.min_depth)
+    }
+
+    fn min_cost_per_metre(&self) -> f64 {
+        1.0 / self.max_depth.max(self.min_depth)
+    }
+}
+
+/// Edge length scaled by `exp(-depth / scale)`, heavily penalising shallow water
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExponentialDepth {
+    pub max_depth: f64,
+    pub scale: f64,
+}
+
+impl CostFunction for ExponentialDepth {
+    fn cost(&self, _: &Bathymetry, neighbor: &Bathymetry, length: f64) -> f64 {
+        length * (-neighbor.depth() / self.scale).exp()
+    }
+
+    fn min_cost_per_metre(&self) -> f64 {
+        (-self.max_depth / self.scale).exp()
+    }
+}
+
+/// Edge length increased by the change in depth along it, avoiding steep banks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slope {
+    pub factor: f64,
+}
+
+impl CostFunction for Slope {
+    fn cost(&self, current: &Bathymetry, neighbor: &Bathymetry, length: f64) -> f64 {
+        length + self.factor * (neighbor.depth() - current.depth()).abs()
+    }
+
+    fn min_cost_per_metre(&self) -> f64 {
+        // reached along flat edges
+        1.0
+    }
+}
+
+/// Built-in cost function and its parameter, written as `name` or `name:parameter`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Cost {
+    Uniform,
+    #[default]
+    Distance,
+    /// Scale in metres
+    DepthRelative(f64),
+    /// Minimum depth in metres
+    InverseDepth(f64),
+    /// Scale in metres
+    ExponentialDepth(f64),
+    /// Metres of extra cost per metre of depth change
+    Slope(f64),
+}
+
+impl Cost {
+    /// Build the cost function for a dataset whose deepest point is `max_depth`
+    pub fn build(self, max_depth: f64) -> Box<dyn CostFunction> {
+        match self {
+            Cost::Uniform => Box::new(Uniform),
+            Cost::Distance => Box::new(Distance),
+            Cost::DepthRelative(scale) => Box::new(DepthRelative { max_depth, scale }),
+            Cost::InverseDepth(min_depth) => Box::new(InverseDepth { max_depth, min_depth }),
+            Cost::ExponentialDepth(scale) => Box::new(ExponentialDepth { max_depth, scale }),
+            Cost::Slope(factor) => Box::new(Slope { factor }),
+        }
+    }
+}
+
+impl str::FromStr for Cost {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let (name, parameter) = match lower.split_once(':') {
+            Some((name, parameter)) => {
+                let value = parameter.trim().parse::<f64>().map_err(|_| "invalid cost parameter")?;
+                if !value.is_finite() || value <= 0.0 {
+                    return Err("cost parameter must be a positive number");
+                }
+                (name.trim(), Some(value))
+            }
+            None => (lower.trim(), None),
+        };
+        match (name, parameter) {
+            ("uniform", None) => Ok(Cost::Uniform),
+            ("distance", None) => Ok(Cost::Distance),
+            ("depth", p) => Ok(Cost::DepthRelative(p.unwrap_or(100.0))),
+            ("inverse", p) => Ok(Cost::InverseDepth(p.unwrap_or(1.0))),
+            ("exponential", p) => Ok(Cost::ExponentialDepth(p.unwrap_or(50.0))),
+            ("slope", p) => Ok(Cost::Slope(p.unwrap_or(10.0))),
+            ("uniform", Some(_)) | ("distance", Some(_)) => Err("cost function takes no parameter"),
+            _ => Err("unrecognized cost function"),
+        }
+    }
+}
+
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cost::Uniform => write!(f, "uniform"),
+            Cost::Distance => write!(f, "distance"),
+            Cost::DepthRelative(scale) => write!(f, "depth:{}", scale),
+            Cost::InverseDepth(min_depth) => write!(f, "inverse:{}", min_depth),
+            Cost::ExponentialDepth(scale) => write!(f, "exponential:{}", scale),
+            Cost::Slope(factor) => write!(f, "slope:{}", factor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deeper_neighbours_are_cheaper() {
+        let current = Bathymetry::new(0.0, 0.0, 50.0);
+        let shallow = Bathymetry::new(0.0, 0.001, 10.0);
+        let deep = Bathymetry::new(0.0, 0.001, 100.0);
+        let costs: Vec<Box<dyn CostFunction>> = vec![
+            Cost::DepthRelative(100.0).build(100.0),
+            Cost::InverseDepth(1.0).build(100.0),
+            Cost::ExponentialDepth(50.0).build(100.0),
+        ];
+        for cost in costs {
+            assert!(cost.cost(&current, &deep, 100.0) < cost.cost(&current, &shallow, 100.0));
+        }
+    }
+
+    #[test]
+    fn depth_relative_scales_with_length() {
+        let neighbor = Bathymetry::new(0.0, 0.0, 40.0);
+        let cost = DepthRelative {
+            max_depth: 140.0,
+            scale: 100.0,
+        };
+        assert_eq!(cost.cost(&neighbor, &neighbor, 1.0), 2.0);
+        assert_eq!(cost.cost(&neighbor, &neighbor, 10.0), 20.0);
+    }
+
+    #[test]
+    fn inverse_depth_does_not_divide_by_zero() {
+        let dry = Bathymetry::new(0.0, 0.0, 0.0);
+        let cost = InverseDepth {
+            max_depth: 100.0,
+            min_depth: 2.0,
+        };
+        assert_eq!(cost.cost(&dry, &dry, 10.0), 5.0);
+    }
+
+    #[test]
+    fn min_cost_per_metre_is_a_lower_bound() {
+        let current = Bathymetry::new(0.0, 0.0, 50.0);
+        let depths = [0.0, 0.5, 10.0, 50.0, 99.0, 100.0];
+        let costs: Vec<Box<dyn CostFunction>> = vec![
+            Cost::Distance.build(100.0),
+            Cost::DepthRelative(100.0).build(100.0),
+            Cost::InverseDepth(1.0).build(100.0),
+            Cost::ExponentialDepth(50.0).build(100.0),
+            Cost::Slope(10.0).build(100.0),
+        ];
+        for cost in costs {
+            assert!(cost.min_cost_per_metre() > 0.0);
+            for &depth in &depths {
+                let neighbor = Bathymetry::new(0.0, 0.001, depth);
+                assert!(cost.cost(&current, &neighbor, 111.0) >= cost.min_cost_per_metre() * 111.0);
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_ignores_length_and_depth() {
+        let shallow = Bathymetry::new(0.0, 0.0, 10.0);
+        let deep = Bathymetry::new(0.0, 0.001, 100.0);
+        let cost = Cost::Uniform.build(100.0);
+        assert_eq!(cost.cost(&shallow, &deep, 10.0), cost.cost(&deep, &shallow, 1000.0));
+        assert_eq!(cost.min_cost_per_metre(), 0.0);
+    }
+
+    #[test]
+    fn slope_penalises_depth_change() {
+        let a = Bathymetry::new(0.0, 0.0, 10.0);
+        let b = Bathymetry::new(0.0, 0.0, 30.0);
+        let cost = Slope { factor: 5.0 };
+        assert_eq!(cost.cost(&a, &b, 100.0), 200.0);
+        assert_eq!(cost.cost(&a, &a, 100.0), 100.0);
+    }
+
+    #[test]
+    fn parse_cost_names_and_parameters() {
+        assert_eq!("Distance".parse(), Ok(Cost::Distance));
+        assert_eq!("uniform".parse(), Ok(Cost::Uniform));
+        assert_eq!("depth".parse(), Ok(Cost::DepthRelative(100.0)));
+        assert_eq!("exponential:25".parse(), Ok(Cost::ExponentialDepth(25.0)));
+        assert_eq!("slope:2.5".parse(), Ok(Cost::Slope(2.5)));
+        assert!("inverse:-1".parse::<Cost>().is_err());
+        assert!("depth:nan".parse::<Cost>().is_err());
+        assert!("inverse:inf".parse::<Cost>().is_err());
+        assert!("slope:nan".parse::<Cost>().is_err());
+        assert!("distance:3".parse::<Cost>().is_err());
+        assert!("uniform:3".parse::<Cost>().is_err());
+        assert!("cheapest".parse::<Cost>().is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let cost = Cost::InverseDepth(2.0);
+        assert_eq!(cost.to_string().parse(), Ok(cost));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::bathymetry::{Bathymetry, Point};
+use crate::error::Error;
+use crate::generator;
+use crate::index::PointIndex;
+
+use geo::algorithm::haversine_destination::HaversineDestination;
+
+use json::{array, object, JsonValue};
+
+// cross-sections are refused when they would take more samples than this in all
+const MAX_SAMPLES: f64 = 10_000_000.0;
+
+/// Depth sampled at a given offset from the thalweg
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Distance from the thalweg in metres, negative to the left and positive to the right
+    pub offset: f64,
+    pub point: Point,
+    pub depth: f64,
+}
+
+/// Transect taken perpendicular to the thalweg at a station
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrossSection {
+    /// Distance of the station from the start of the thalweg in metres
+    pub distance: f64,
+    pub centre: Point,
+    pub samples: Vec<Sample>,
+}
+
+pub(crate) fn cross_sections(
+    path: &[Bathymetry],
+    points: &dyn PointIndex,
+    spacing: f64,
+    half_width: f64,
+    step: f64,
+) -> Result<Vec<CrossSection>, Error> {
+    for (name, value) in [("spacing", spacing), ("half width", half_width), ("resolution", step)] {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(Error::InvalidInput(format!("{} of {} m is not a positive distance", name, value)));
+        }
+    }
+    let length: f64 = path.windows(2).map(|pair| pair[0].distance_to(&pair[1])).sum();
+    // a path with no length has no direction to take transects across
+    if length == 0.0 {
+        return Ok(vec![]);
+    }
+    // stations every spacing from the start, and one more on the end
+    let stations = (length / spacing).floor() + 2.0;
+    let samples_per_side = (half_width / step).floor();
+    if stations * (2.0 * samples_per_side + 1.0) > MAX_SAMPLES {
+        return Err(Error::InvalidInput(format!(
+            "cross-sections {} m wide every {} m along a path {:.0} m long would take too many samples",
+            2.0 * half_width,
+            spacing,
+            length
+        )));
+    }
+    let samples_per_side = samples_per_side as isize;
+    Ok(generator::stations(path, spacing)
+        .into_iter()
+        .map(|station| {
+            let centre = geo::Point::from(station.point);
+            let samples = (-samples_per_side..=samples_per_side)
+                .filter_map(|i| {
+                    let offset = i as f64 * step;
+                    let location = centre.haversine_destination(station.bearing + 90.0, offset).x_y();
+                    points
+                        .nearest(&location)
+                        .map(|id| points.get(id))
+                        // gaps in the data are most likely land
+                        .filter(|nearest| nearest.distance_to_point(&location) <= step)
+                        .map(|nearest| Sample {
+                            offset,
+                            point: location,
+                            depth: nearest.depth(),
+                        })
+                })
+                .collect();
+            CrossSection {
+                distance: station.distance,
+                centre: station.point,
+                samples,
+            }
+        })
+        .collect())
+}
+
+/// Distances in km and offsets in m, one row per sample
+pub fn to_csv(sections: &[CrossSection]) -> String {
+    let mut out = String::from("station,distance,offset,longitude,latitude,depth\n");
+    for (station, section) in sections.iter().enumerate() {
+        for sample in &section.samples {
+            let (lon, lat) = sample.point;
+            out += format!(
+                "{},{},{},{},{},{}\n",
+                station,
+                section.distance / 1000.0,
+                sample.offset,
+                lon,
+                lat,
+                sample.depth
+            )
+            .as_str();
+        }
+    }
+    out
+}
+
+/// One 3D `LineString` feature per station, with depth represented as negative elevation
+pub fn to_geojson(sections: &[CrossSection]) -> String {
+    let mut features = JsonValue::new_array();
+    for (station, section) in sections.iter().enumerate() {
+        let mut coordinates = JsonValue::new_array();
+        for sample in &section.samples {
+            let (lon, lat) = sample.point;
+            // pushing onto an array cannot fail
+            let _ = coordinates.push(array![lon, lat, -sample.depth]);
+        }
+        let _ = features.push(object! {
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": coordinates,
+            },
+            "properties": {
+                "station": station,
+                "distance": section.distance / 1000.0,
+            },
+        });
+    }
+    object! {
+        "type": "FeatureCollection",
+        "features": features,
+    }
+    .dump()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::TreeIndex;
+
+    // channel running east along the equator, deepest in the middle
+    fn channel() -> TreeIndex {
+        let mut points = vec![];
+        for x in 0..=20 {
+            for y in -5..=5 {
+                let depth = 100.0 - 15.0 * (y as f64).abs();
+                points.push(Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, depth));
+            }
+        }
+        TreeIndex::new(points)
+    }
+
+    #[test]
+    fn stations_are_evenly_spaced() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 100.0), Bathymetry::new(0.0, 0.02, 100.0)];
+        let sections = cross_sections(&path, &channel(), 1000.0, 300.0, 100.0).unwrap();
+        let distances: Vec<f64> = sections.iter().map(|s| s.distance).collect();
+        // the last station is on the end of the thalweg, as when resampling
+        assert_eq!(distances, vec![0.0, 1000.0, 2000.0, path[0].distance_to(&path[1])]);
+        assert_eq!(sections[3].centre, path[1].point());
+    }
+
+    #[test]
+    fn samples_are_perpendicular_to_thalweg() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 100.0), Bathymetry::new(0.0, 0.02, 100.0)];
+        let sections = cross_sections(&path, &channel(), 1000.0, 300.0, 100.0).unwrap();
+        let section = &sections[1];
+        assert_eq!(section.samples.len(), 7);
+        for sample in &section.samples {
+            let (lon, lat) = sample.point;
+            assert!((lon - section.centre.0).abs() < 1e-6);
+            // heading east, so the right hand side is south
+            assert_eq!(lat < 0.0, sample.offset > 0.0);
+        }
+        let deepest = section
+            .samples
+            .iter()
+            .max_by(|a, b| a.depth.partial_cmp(&b.depth).unwrap())
+            .unwrap();
+        assert_eq!(deepest.offset, 0.0);
+    }
+
+    #[test]
+    fn samples_outside_data_are_skipped() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 100.0), Bathymetry::new(0.0, 0.02, 100.0)];
+        let sections = cross_sections(&path, &channel(), 1000.0, 1000.0, 100.0).unwrap();
+        // data ends about 556 m either side, so samples beyond 656 m find nothing within a step
+        assert_eq!(sections[1].samples.len(), 13);
+    }
+
+    #[test]
+    fn single_point_has_no_cross_sections() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 100.0)];
+        assert!(cross_sections(&path, &channel(), 1000.0, 300.0, 100.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unusable_distances_are_rejected() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 100.0), Bathymetry::new(0.0, 0.02, 100.0)];
+        for half_width in [0.0, -300.0, f64::NAN, f64::INFINITY] {
+            let result = cross_sections(&path, &channel(), 1000.0, half_width, 100.0);
+            assert!(matches!(result, Err(Error::InvalidInput(_))));
+        }
+        let result = cross_sections(&path, &channel(), f64::NAN, 300.0, 100.0);
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn too_many_samples_are_refused() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 100.0), Bathymetry::new(0.0, 0.02, 100.0)];
+        let result = cross_sections(&path, &channel(), 1000.0, 1e12, 100.0);
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+        let result = cross_sections(&path, &channel(), 0.001, 300.0, 100.0);
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn to_csv_one_sample() {
+        let sections = vec![CrossSection {
+            distance: 1500.0,
+            centre: (-123.7, 48.7),
+            samples: vec![Sample {
+                offset: -100.0,
+                point: (-123.7, 48.7),
+                depth: 100.4,
+            }],
+        }];
+        let expected = "station,distance,offset,longitude,latitude,depth\n0,1.5,-100,-123.7,48.7,100.4\n";
+        assert_eq!(to_csv(&sections), expected);
+    }
+
+    #[test]
+    fn to_geojson_one_sample() {
+        let sections = vec![CrossSection {
+            distance: 1500.0,
+            centre: (-123.7, 48.7),
+            samples: vec![Sample {
+                offset: -100.0,
+                point: (-123.7, 48.7),
+                depth: 100.4,
+            }],
+        }];
+        let expected = concat!(
+            "{\"type\":\"FeatureCollection\",\"features\":[{",
+            "\"type\":\"Feature\",",
+            "\"geometry\":{\"type\":\"LineString\",\"coordinates\":[[-123.7,48.7,-100.4]]},",
+            "\"properties\":{\"station\":0,\"distance\":1.5}",
+            "}]}"
+        );
+        assert_eq!(to_geojson(&sections), expected);
+    }
+}
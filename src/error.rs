@@ -0,0 +1,175 @@
+use crate::bathymetry::Point;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors produced while reading data or generating a thalweg
+#[derive(Debug)]
+pub enum Error {
+    /// Underlying I/O failure
+    Io(io::Error),
+    /// A line of input could not be understood
+    Parse {
+        file: Option<PathBuf>,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A required column is not present in a header
+    MissingColumn(String),
+    /// GeoJSON contained a different geometry than the one required
+    WrongGeometry {
+        expected: &'static str,
+        found: String,
+    },
+    /// File structure or metadata is not usable, e.g. a raster without georeferencing
+    InvalidInput(String),
+    /// The search could not connect two points
+    NoPath { source: Point, sink: Point },
+    /// The search could not connect two waypoints, given by their positions in the list of waypoints
+    NoSegmentPath {
+        from: usize,
+        to: usize,
+        source: Point,
+        sink: Point,
+    },
+    /// There is no data to work with
+    EmptyDataset,
+    /// A plot could not be rendered
+    Render(String),
+    /// GeoJSON could not be parsed as JSON
+    Json(json::Error),
+    /// A GeoTIFF could not be decoded
+    Tiff(tiff::TiffError),
+    /// A NetCDF file could not be opened or read
+    #[cfg(feature = "netcdf")]
+    NetCdf(netcdf::Error),
+}
+
+impl Error {
+    pub(crate) fn parse(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Error::Parse {
+            file: None,
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// Attach the name of the file being read to a parse error
+    pub fn with_file<P: AsRef<Path>>(self, path: P) -> Self {
+        match self {
+            Error::Parse {
+                line,
+                column,
+                message,
+                ..
+            } => Error::Parse {
+                file: Some(path.as_ref().to_path_buf()),
+                line,
+                column,
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse {
+                file: Some(file),
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", file.display(), line, column, message),
+            Error::Parse {
+                file: None,
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            Error::MissingColumn(name) => write!(f, "{} not found", name),
+            Error::WrongGeometry { expected, found } => {
+                write!(f, "expected a {} object but found {}", expected, found)
+            }
+            Error::InvalidInput(message) => write!(f, "{}", message),
+            Error::NoPath { source, sink } => {
+                write!(f, "No path found between {:?} and {:?}", source, sink)
+            }
+            Error::NoSegmentPath { from, to, source, sink } => write!(
+                f,
+                "No path found from waypoint {} {:?} to waypoint {} {:?}",
+                from + 1,
+                source,
+                to + 1,
+                sink
+            ),
+            Error::EmptyDataset => write!(f, "No bathymetry data available"),
+            Error::Render(message) => write!(f, "{}", message),
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Tiff(e) => write!(f, "{}", e),
+            #[cfg(feature = "netcdf")]
+            Error::NetCdf(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Tiff(e) => Some(e),
+            #[cfg(feature = "netcdf")]
+            Error::NetCdf(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(e: json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<tiff::TiffError> for Error {
+    fn from(e: tiff::TiffError) -> Self {
+        Error::Tiff(e)
+    }
+}
+
+#[cfg(feature = "netcdf")]
+impl From<netcdf::Error> for Error {
+    fn from(e: netcdf::Error) -> Self {
+        Error::NetCdf(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_reports_position() {
+        let error = Error::parse(3, 2, "Invalid longitude");
+        assert_eq!(error.to_string(), "line 3, column 2: Invalid longitude");
+    }
+
+    #[test]
+    fn parse_error_reports_file() {
+        let error = Error::parse(3, 2, "Invalid longitude").with_file("tile.txt");
+        assert_eq!(error.to_string(), "tile.txt:3:2: Invalid longitude");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::bathymetry::Bathymetry;
+use crate::generator::Priority;
+use crate::index::{GridIndex, PointIndex};
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// rise given to each cell over the one it drains to when filling, so filled flats still drain
+const EPSILON: f64 = 1e-6;
+
+const NONE: usize = usize::MAX;
+
+/// Drainage of gridded bathymetry towards a single outlet, treating negated depth as terrain
+///
+/// Depressions are filled by flooding outwards from the outlet, lowest cell first, so that every point connected
+/// to the outlet drains to it. Each point then flows to the neighbour of the eight around it with the steepest
+/// descent across the filled surface (D8), and the flow accumulation of a point counts the points draining
+/// through it, itself included.
+pub struct FlowRouting {
+    /// Id of the point each point flows to, the outlet for itself, or `usize::MAX` where it cannot reach the outlet
+    downstream: Vec<usize>,
+    accumulation: Vec<usize>,
+    outlet: usize,
+}
+
+impl FlowRouting {
+    /// Route flow to `outlet` between neighbouring points for which `connected` holds
+    pub fn new(grid: &GridIndex, outlet: usize, connected: &dyn Fn(&Bathymetry, &Bathymetry) -> bool) -> Self {
+        let nodes = grid.nodes();
+        let width = grid.columns().count;
+        let height = grid.rows().count;
+        let cells = grid.cells();
+        let mut cell_of = vec![NONE; nodes.len()];
+        for (cell, &id) in cells.iter().enumerate().filter(|&(_, &id)| id != NONE) {
+            cell_of[id] = cell;
+        }
+        let neighbours = |id: usize| {
+            let (column, row) = ((cell_of[id] % width) as isize, (cell_of[id] / width) as isize);
+            (-1..=1)
+                .flat_map(move |dr| (-1..=1).map(move |dc| (column + dc, row + dr)))
+                .filter(move |&(c, r)| (c, r) != (column, row) && c >= 0 && r >= 0)
+                .filter(move |&(c, r)| (c as usize) < width && (r as usize) < height)
+                .map(move |(c, r)| cells[r as usize * width + c as usize])
+                .filter(move |&neighbour| neighbour != NONE && connected(&nodes[id], &nodes[neighbour]))
+        };
+
+        // priority flood: every point is raised to at least just above the point it was reached from
+        let mut filled = vec![f64::NAN; nodes.len()];
+        let mut order = vec![];
+        let mut queue = BinaryHeap::new();
+        filled[outlet] = -nodes[outlet].depth();
+        queue.push(Reverse((Priority(filled[outlet]), outlet)));
+        while let Some(Reverse((_, id))) = queue.pop() {
+            order.push(id);
+            for neighbour in neighbours(id) {
+                if filled[neighbour].is_nan() {
+                    filled[neighbour] = f64::max(-nodes[neighbour].depth(), filled[id] + EPSILON);
+                    queue.push(Reverse((Priority(filled[neighbour]), neighbour)));
+                }
+            }
+        }
+
+        // steepest descent, which always exists as the point each was flooded from is lower
+        let mut downstream = vec![NONE; nodes.len()];
+        downstream[outlet] = outlet;
+        for &id in &order[1..] {
+            let slope = |neighbour: usize| (filled[id] - filled[neighbour]) / nodes[id].distance_to(&nodes[neighbour]);
+            downstream[id] = neighbours(id)
+                .filter(|&neighbour| filled[neighbour] < filled[id])
+                .max_by(|&a, &b| slope(a).total_cmp(&slope(b)))
+                .expect("filled point with no lower neighbour");
+        }
+
+        // points are flooded in rising order, so every point comes after the one it drains to
+        let mut accumulation = vec![0; nodes.len()];
+        for &id in order.iter().rev() {
+            accumulation[id] += 1;
+            if id != outlet {
+                accumulation[downstream[id]] += accumulation[id];
+            }
+        }
+
+        Self {
+            downstream,
+            accumulation,
+            outlet,
+        }
+    }
+
+    /// Id of the point each point flows to, the outlet for itself, or `usize::MAX` where it cannot reach the outlet
+    pub fn downstream(&self) -> &[usize] {
+        &self.downstream
+    }
+
+    /// Number of points draining through a point, itself included, or zero where it cannot reach the outlet
+    pub fn accumulation(&self, id: usize) -> usize {
+        self.accumulation[id]
+    }
+
+    /// Ids of the points along the channel of maximum accumulation, from its head to the outlet.
+    /// Upstream from the outlet, each step is to the point draining into it with the greatest accumulation
+    pub fn channel(&self) -> Vec<usize> {
+        let mut upstream = vec![NONE; self.downstream.len()];
+        for (id, &next) in self.downstream.iter().enumerate() {
+            if next != NONE && next != id {
+                let best = upstream[next];
+                if best == NONE || self.accumulation[id] > self.accumulation[best] {
+                    upstream[next] = id;
+                }
+            }
+        }
+        let mut out = vec![self.outlet];
+        let mut id = self.outlet;
+        while upstream[id] != NONE {
+            id = upstream[id];
+            out.push(id);
+        }
+        out.reverse();
+        out
+    }
+
+    /// Ids of the points along the flow path from `id` to the outlet
+    pub fn path(&self, mut id: usize) -> Option<Vec<usize>> {
+        let mut out = vec![id];
+        loop {
+            match self.downstream[id] {
+                NONE => return None,
+                next if next == id => return Some(out),
+                next => {
+                    out.push(next);
+                    id = next;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // channel along a row of a grid, with a basin part way along and an unconnected pool to one side
+    fn inlet() -> GridIndex {
+        let depths = [
+            [1.0, 2.0, 2.0, 2.0, 2.0, 1.0],
+            [10.0, 20.0, 40.0, 15.0, 30.0, 35.0],
+            [1.0, 2.0, 2.0, 2.0, 2.0, 1.0],
+            [f64::NAN, f64::NAN, f64::NAN, f64::NAN, f64::NAN, f64::NAN],
+            [5.0, f64::NAN, f64::NAN, f64::NAN, f64::NAN, f64::NAN],
+        ];
+        let mut nodes = vec![];
+        for (row, values) in depths.iter().enumerate() {
+            for (column, &depth) in values.iter().enumerate() {
+                if !depth.is_nan() {
+                    nodes.push(Bathymetry::new(row as f64 * 0.001, column as f64 * 0.001, depth));
+                }
+            }
+        }
+        GridIndex::new(nodes).ok().unwrap()
+    }
+
+    #[test]
+    fn flow_follows_the_channel_through_a_basin() {
+        let grid = inlet();
+        let outlet = grid.nearest(&(0.005, 0.001)).unwrap();
+        let routing = FlowRouting::new(&grid, outlet, &|_, _| true);
+        let source = grid.nearest(&(0.0, 0.001)).unwrap();
+        let path: Vec<_> = routing.path(source).unwrap().iter().map(|&id| grid.get(id).point()).collect();
+        let channel: Vec<_> = (0..6).map(|column| (column as f64 * 0.001, 0.001)).collect();
+        assert_eq!(path, channel);
+        // every connected point drains through the outlet
+        assert_eq!(routing.accumulation(outlet), 18);
+        assert!(routing.accumulation(source) < routing.accumulation(grid.nearest(&(0.003, 0.001)).unwrap()));
+        // the channel heads on the bank beside the source, which drains into it
+        assert_eq!(routing.channel()[1..], routing.path(source).unwrap()[1..]);
+        assert_eq!(routing.path(grid.nearest(&(0.0, 0.004)).unwrap()), None);
+    }
+
+    fn grid(depths: &[[f64; 6]]) -> GridIndex {
+        let mut nodes = vec![];
+        for (row, values) in depths.iter().enumerate() {
+            for (column, &depth) in values.iter().enumerate() {
+                nodes.push(Bathymetry::new(row as f64 * 0.001, column as f64 * 0.001, depth));
+            }
+        }
+        GridIndex::new(nodes).ok().unwrap()
+    }
+
+    #[test]
+    fn channel_of_maximum_accumulation_leaves_the_descent() {
+        // bank along the first row, falling towards the outlet faster than it falls into the channel beside it
+        let grid = grid(&[
+            [5.0, 10.0, 15.0, 20.0, 25.0, 30.0],
+            [6.0, 12.0, 18.0, 24.0, 30.0, 36.0],
+            [1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        ]);
+        let outlet = grid.nearest(&(0.005, 0.001)).unwrap();
+        let routing = FlowRouting::new(&grid, outlet, &|_, _| true);
+        let points = |ids: Vec<usize>| ids.iter().map(|&id| grid.get(id).point()).collect::<Vec<_>>();
+        let channel: Vec<_> = (0..6).map(|column| (column as f64 * 0.001, 0.001)).collect();
+        assert_eq!(points(routing.channel()), channel);
+        // descending from the corner runs down the bank and only joins the channel part way along
+        let descent = points(routing.path(grid.nearest(&(0.0, 0.0)).unwrap()).unwrap());
+        assert_eq!(descent[..3], [(0.0, 0.0), (0.001, 0.0), (0.002, 0.001)]);
+        assert_eq!(descent[2..], channel[2..]);
+    }
+
+    #[test]
+    fn flow_respects_connections() {
+        let grid = inlet();
+        let outlet = grid.nearest(&(0.005, 0.001)).unwrap();
+        // nothing may cross the line between the third and fourth columns
+        let routing = FlowRouting::new(&grid, outlet, &|a, b| (a.point().0 < 0.0025) == (b.point().0 < 0.0025));
+        assert_eq!(routing.path(grid.nearest(&(0.0, 0.001)).unwrap()), None);
+        assert_eq!(routing.accumulation(outlet), 9);
+    }
+}
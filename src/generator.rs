@@ -1,118 +1,436 @@
+use crate::analysis;
 use crate::bathymetry::{Bathymetry, Point};
+use crate::centreline;
+use crate::cross_section::{self, CrossSection};
+use crate::cost::{Cost, CostFunction};
+use crate::error::Error;
+use crate::flow::FlowRouting;
+use crate::index::{self, Layout, PointIndex};
+use crate::interpolate::{self, Interpolation};
+use crate::land::LandMask;
+use crate::network::Branch;
+use crate::section;
+use crate::simplify::{self, Simplification};
+use crate::smooth::{self, Smoothing};
 
-use std::cmp::Reverse;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
+use std::fmt;
+use std::str;
 
-use geo::algorithm::geodesic_length::GeodesicLength;
+use geo::algorithm::bearing::Bearing;
+use geo::algorithm::haversine_destination::HaversineDestination;
 use geo::algorithm::line_interpolate_point::LineInterpolatePoint;
-use geo::algorithm::simplifyvw::SimplifyVWPreserve;
-use geo::{Line, LineString};
+use geo::Line;
 
 use priority_queue::PriorityQueue;
 
-use rstar::RTree;
+// resampling is refused when it would place more points than this along the path
+const MAX_STATIONS: f64 = 1_000_000.0;
+
+/// Queue priority keeping full floating point precision
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Priority(pub(crate) f64);
+
+impl Eq for Priority {}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// How the thalweg between two points is found
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Method {
+    /// Minimum-cost path through the bathymetry, see `cost`
+    #[default]
+    Search,
+    /// Path water would take over the bathymetry as terrain, with depressions filled, see `flow::FlowRouting`.
+    /// Only available for gridded bathymetry
+    Flow,
+    /// Line along the middle of the water, away from both banks, see `centreline::centreline`
+    Centre,
+}
+
+impl str::FromStr for Method {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "search" => Ok(Method::Search),
+            "flow" => Ok(Method::Flow),
+            "centre" | "center" => Ok(Method::Centre),
+            _ => Err("unrecognized method"),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Search => write!(f, "search"),
+            Method::Flow => write!(f, "flow"),
+            Method::Centre => write!(f, "centre"),
+        }
+    }
+}
+
+/// A point the thalweg should pass through
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Waypoint {
+    pub point: Point,
+    /// Optional waypoints are left out when no path through them exists
+    pub optional: bool,
+}
+
+impl Waypoint {
+    pub fn mandatory(point: Point) -> Self {
+        Self { point, optional: false }
+    }
+
+    pub fn optional(point: Point) -> Self {
+        Self { point, optional: true }
+    }
+}
+
+impl From<Point> for Waypoint {
+    fn from(point: Point) -> Self {
+        Self::mandatory(point)
+    }
+}
 
 pub struct ThalwegGenerator {
-    points: RTree<Bathymetry>,
+    points: Box<dyn PointIndex>,
     max_depth: f64,
     resolution: usize,
-    weighted: bool,
+    cost: Box<dyn CostFunction>,
+    land: Option<LandMask>,
+    method: Method,
+    interpolation: Interpolation,
+    interpolation_reach: Option<f64>,
 }
 
 impl ThalwegGenerator {
+    /// Weighted generators prefer deeper points using `Cost::DepthRelative`, others find the shortest path
     pub fn new(points: Vec<Bathymetry>, resolution: usize, weighted: bool) -> Self {
+        Self::from_index(index::auto(points), resolution, weighted)
+    }
+
+    /// Search points already stored in either backend, see `index::build`
+    pub fn from_index(points: Box<dyn PointIndex>, resolution: usize, weighted: bool) -> Self {
         let max_depth = points
+            .nodes()
             .iter()
             .map(Bathymetry::depth)
             .reduce(f64::max)
             .unwrap_or(0.0);
+        let cost = if weighted { Cost::DepthRelative(100.0) } else { Cost::Distance };
         Self {
-            points: RTree::bulk_load(points),
+            points,
             max_depth,
             resolution,
-            weighted,
+            cost: cost.build(max_depth),
+            land: None,
+            method: Method::default(),
+            interpolation: Interpolation::default(),
+            interpolation_reach: None,
+        }
+    }
+
+    /// Replace the cost of moving between neighbouring points in the search
+    pub fn with_cost_function(mut self, cost: Box<dyn CostFunction>) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Depth of the deepest point available
+    pub fn max_depth(&self) -> f64 {
+        self.max_depth
+    }
+
+    /// Forbid paths from crossing land and points from being taken from across the shoreline
+    pub fn with_land_mask(mut self, land: LandMask) -> Self {
+        self.land = Some(land);
+        self
+    }
+
+    /// Choose how thalwegs between points are found
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Choose how depths are found where a path is densified or laid over the bathymetry: snapping to the nearest
+    /// sounding, or interpolating at the exact position. Bilinear interpolation needs gridded bathymetry.
+    /// Inverse-distance weighting and triangulation take soundings from up to the resolution away unless
+    /// `with_interpolation_reach` says otherwise
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Result<Self, Error> {
+        if interpolation == Interpolation::Bilinear && matches!(self.points.layout(), Layout::Scattered) {
+            return Err(Error::InvalidInput(
+                "bilinear interpolation needs bathymetry on a regular grid".to_string(),
+            ));
+        }
+        self.interpolation = interpolation;
+        Ok(self)
+    }
+
+    /// Interpolate by inverse-distance weighting or triangulation from soundings up to `reach` metres from a
+    /// position, in place of the resolution. Sparse soundings need a longer reach than the spacing of the path
+    pub fn with_interpolation_reach(mut self, reach: f64) -> Self {
+        self.interpolation_reach = Some(reach);
+        self
+    }
+
+    fn crosses_land(&self, a: Point, b: Point) -> bool {
+        self.land.as_ref().is_some_and(|land| land.crosses(a, b))
+    }
+
+    /// Thalweg between the points nearest to `source` and `sink`: the minimum-cost path found with A*,
+    /// with `Method::Flow` the path water would take to the sink as outlet along the channel of maximum accumulation
+    /// where it passes within the resolution of the source,
+    /// or with `Method::Centre` the points nearest the centreline of the water
+    pub fn thalweg(&self, source: Point, sink: Point) -> Result<Vec<Bathymetry>, Error> {
+        let source_id = self.nearest_to_end(source).ok_or(Error::EmptyDataset)?;
+        let sink_id = self.nearest_to_end(sink).ok_or(Error::EmptyDataset)?;
+        let path = match self.method {
+            Method::Search => {
+                let previous = self.search(source_id, &[sink_id]);
+                self.trace(&previous, source_id, sink_id)
+            }
+            Method::Flow => {
+                let routing = self.flow_routing(sink_id)?;
+                routing
+                    .path(self.onto_channel(&routing, source_id))
+                    .map(|ids| ids.iter().map(|&id| self.points.get(id).clone()).collect())
+            }
+            Method::Centre => self.centreline(source, sink),
+        };
+        path.ok_or(Error::NoPath { source, sink })
+    }
+
+    /// Points nearest the line along the middle of the water between `source` and `sink`, snapped as by `from_path`
+    pub fn centreline(&self, source: Point, sink: Point) -> Option<Vec<Bathymetry>> {
+        let line = centreline::centreline(self.points.as_ref(), self.land.as_ref(), source, sink)?;
+        let mut path = self.from_path(&line);
+        path.dedup();
+        Some(path)
+    }
+
+    /// Drainage of the bathymetry to `outlet` without crossing land, if it is gridded
+    pub fn flow_routing(&self, outlet: usize) -> Result<FlowRouting, Error> {
+        match self.points.layout() {
+            Layout::Grid(grid) => Ok(FlowRouting::new(grid, outlet, &|a, b| {
+                !self.crosses_land(a.point(), b.point())
+            })),
+            Layout::Scattered => Err(Error::InvalidInput(
+                "flow routing needs bathymetry on a regular grid".to_string(),
+            )),
         }
     }
 
-    pub fn thalweg(&self, source: Point, sink: Point) -> Option<Vec<Bathymetry>> {
-        let source_in_tree = self.points.nearest_neighbor(&source)?;
-        let sink_in_tree = self.points.nearest_neighbor(&sink)?;
+    /// Point of the channel of maximum accumulation nearest `source_id`, if one is within the resolution of it
+    /// and can be reached without crossing land, otherwise `source_id` itself
+    fn onto_channel(&self, routing: &FlowRouting, source_id: usize) -> usize {
+        let source = self.points.get(source_id);
+        routing
+            .channel()
+            .into_iter()
+            .map(|id| (id, source.distance_to(self.points.get(id))))
+            .filter(|&(id, distance)| {
+                distance <= self.resolution as f64 && !self.crosses_land(source.point(), self.points.get(id).point())
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(source_id, |(id, _)| id)
+    }
 
-        // RTree uses distance^2 in locate_within_distance
-        let distance_squared = (self.resolution * self.resolution) as f64;
+    /// A* search from `source_id` until each of `sink_ids` has been reached by its minimum-cost path,
+    /// giving the point before each point on the best path found to it, or `usize::MAX` where there is none
+    fn search(&self, source_id: usize, sink_ids: &[usize]) -> Vec<usize> {
+        let sinks: Vec<&Bathymetry> = sink_ids.iter().map(|&id| self.points.get(id)).collect();
+        let mut remaining = sink_ids.to_vec();
+        remaining.sort_unstable();
+        remaining.dedup();
+        // no edge is cheaper per metre than this, and no path is shorter than the straight line to the nearest sink,
+        // so the heuristic never overestimates and the first time a sink is taken from the queue is optimal
+        let cost_per_metre = self.cost.min_cost_per_metre();
 
-        let mut state = HashMap::new();
-        state.insert(source_in_tree, (0.0, source_in_tree));
+        // cost of the best path found so far to each point, and the point before it on that path
+        let mut distance = vec![f64::INFINITY; self.points.len()];
+        let mut previous = vec![usize::MAX; self.points.len()];
+        distance[source_id] = 0.0;
+        previous[source_id] = source_id;
         let mut work_queue = PriorityQueue::new();
-        work_queue.push(source_in_tree, Reverse(0));
-        let mut weights = HashMap::new();
+        work_queue.push(source_id, Reverse(Priority(0.0)));
 
-        while let Some((current, _)) = work_queue.pop() {
-            let distance_to_here = state.get(current).map(|&(d, _)| d).unwrap_or(f64::INFINITY);
+        while let Some((current_id, _)) = work_queue.pop() {
+            if let Ok(index) = remaining.binary_search(&current_id) {
+                remaining.remove(index);
+                if remaining.is_empty() {
+                    break;
+                }
+            }
+            let current = self.points.get(current_id);
+            let distance_to_here = distance[current_id];
 
-            for neighbor in self
+            for neighbor_id in self
                 .points
-                .locate_within_distance(current.point(), distance_squared)
+                .within(&current.point(), self.resolution as f64)
+                .filter(|&neighbor_id| neighbor_id != current_id)
             {
+                let neighbor = self.points.get(neighbor_id);
+                if self.crosses_land(current.point(), neighbor.point()) {
+                    continue;
+                }
                 // use A* names for to make comparison easier
-                let g_n = distance_to_here
-                    + *weights
-                        .entry(neighbor)
-                        .or_insert_with_key(|key| self.weight_of(key));
-                let old_distance = state
-                    .get(&neighbor)
-                    .map(|&(d, _)| d)
-                    .unwrap_or(f64::INFINITY);
-                if g_n < old_distance {
-                    state.insert(neighbor, (g_n, current));
-                    let h_n = neighbor.distance_to(sink_in_tree);
+                let g_n = distance_to_here + self.cost.cost(current, neighbor, current.distance_to(neighbor));
+                if g_n < distance[neighbor_id] {
+                    distance[neighbor_id] = g_n;
+                    previous[neighbor_id] = current_id;
+                    let h_n = cost_per_metre
+                        * sinks
+                            .iter()
+                            .map(|sink| neighbor.distance_to(sink))
+                            .fold(f64::INFINITY, f64::min);
                     let f_n = g_n + h_n;
                     // push_increase will do the insertion as normal if the neighbor is not already present,
                     // but will modify the priority if we hand it a "larger" one. Most descriptions of A* use a
                     // min heap, where decrease_priority works as expected. However, work_queue is implemented
                     // as a max heap, which we are tricking into becoming a min heap with Reverse(). Because of
                     // this, increased priorities are actually smaller numbers, as desired.
-                    work_queue.push_increase(neighbor, Reverse(f_n as isize));
+                    work_queue.push_increase(neighbor_id, Reverse(Priority(f_n)));
                 }
             }
-
-            if current == sink_in_tree {
-                break;
-            }
         }
+        previous
+    }
 
-        if !state.contains_key(sink_in_tree) {
+    /// Path from `source_id` to `sink_id` found by `search`, if it reached the sink
+    fn trace(&self, previous: &[usize], source_id: usize, sink_id: usize) -> Option<Vec<Bathymetry>> {
+        if previous[sink_id] == usize::MAX {
             return None;
         }
-
         let mut path = vec![];
-        let mut current = sink_in_tree;
-        while current != source_in_tree {
-            path.push(current.clone());
-            current = self
-                .points
-                .nearest_neighbor(&state.get(&current).map(|&(_, p)| p.clone())?.point())?;
+        let mut current_id = sink_id;
+        while current_id != source_id {
+            path.push(self.points.get(current_id).clone());
+            current_id = previous[current_id];
         }
-        path.push(current.clone());
+        path.push(self.points.get(source_id).clone());
 
         path.reverse();
         Some(path)
     }
 
-    fn weight_of(&self, point: &Bathymetry) -> f64 {
-        if self.weighted {
-            let scale = 100.0;
-            (self.max_depth - point.depth() + scale) / scale
-        } else {
-            1.0
+    /// Thalwegs from `mouth` to each of `heads`, sharing their common course as a tree of branches.
+    /// Branch `i` ends at head `i` and starts where it leaves an earlier branch, or at the mouth for the first.
+    /// The branches follow the minimum-cost paths from the mouth, or with `Method::Flow` the flow paths to it,
+    /// either of which never part and join again
+    pub fn network(&self, mouth: Point, heads: &[Point]) -> Result<Vec<Branch>, Error> {
+        let mouth_id = self.nearest_to_end(mouth).ok_or(Error::EmptyDataset)?;
+        let head_ids = heads
+            .iter()
+            .map(|&head| self.nearest_to_end(head).ok_or(Error::EmptyDataset))
+            .collect::<Result<Vec<_>, _>>()?;
+        // either way, the point after each on its way back to the mouth
+        let previous = match self.method {
+            Method::Search => self.search(mouth_id, &head_ids),
+            Method::Flow => self.flow_routing(mouth_id)?.downstream().to_vec(),
+            Method::Centre => {
+                return Err(Error::InvalidInput(
+                    "networks can only be found by search or flow".to_string(),
+                ))
+            }
+        };
+
+        // branch holding each point already in the network, and its distance from the mouth
+        let mut joined: HashMap<usize, (usize, isize)> = HashMap::new();
+        let mut branches = vec![];
+        for (id, (&head, &head_id)) in heads.iter().zip(&head_ids).enumerate() {
+            if previous[head_id] == usize::MAX {
+                return Err(Error::NoPath { source: mouth, sink: head });
+            }
+            // follow the path back from the head until it meets the network, or the mouth for the first branch
+            let mut ids = vec![head_id];
+            let mut current_id = head_id;
+            while current_id != mouth_id && !joined.contains_key(&current_id) {
+                current_id = previous[current_id];
+                ids.push(current_id);
+            }
+            ids.reverse();
+            let (parent, start) = match joined.get(&ids[0]) {
+                Some(&(parent, start)) => (Some(parent), start),
+                None => (None, 0),
+            };
+            let branch = Branch {
+                id,
+                parent,
+                start,
+                path: ids.iter().map(|&point_id| self.points.get(point_id).clone()).collect(),
+            };
+            for (&point_id, section) in ids.iter().zip(branch.section()) {
+                joined.entry(point_id).or_insert((id, section.distance()));
+            }
+            branches.push(branch);
         }
+        Ok(branches)
+    }
+
+    /// Thalweg passing through each of `points` in turn, see `thalweg_via`
+    pub fn thalweg_through(&self, points: &[Point]) -> Result<Vec<Bathymetry>, Error> {
+        let waypoints: Vec<Waypoint> = points.iter().map(|&point| Waypoint::from(point)).collect();
+        self.thalweg_via(&waypoints)
+    }
+
+    /// Thalweg passing through each of `waypoints` in turn, joining the segments between them without repeating
+    /// the shared points.
+    /// Optional waypoints are dropped when no path through them exists, but the first and last are always visited.
+    /// If a segment between waypoints that must be visited cannot be found, `Error::NoSegmentPath` says which
+    pub fn thalweg_via(&self, waypoints: &[Waypoint]) -> Result<Vec<Bathymetry>, Error> {
+        let last = match waypoints.len() {
+            0 => return Ok(vec![]),
+            1 => return Ok(self.from_path(&[waypoints[0].point])),
+            len => len - 1,
+        };
+        // waypoints visited so far, each with the segment leading to it
+        let mut visited = vec![(0, vec![])];
+        let mut to = 1;
+        while to <= last {
+            let from = visited.last().map_or(0, |&(from, _)| from);
+            match self.thalweg(waypoints[from].point, waypoints[to].point) {
+                Ok(segment) => {
+                    visited.push((to, segment));
+                    to += 1;
+                }
+                // neighbours are symmetric, so a waypoint that cannot be reached from one visited cannot be reached
+                // from any of them, and dropping it is the only way on
+                Err(Error::NoPath { .. }) if waypoints[to].optional && to < last => to += 1,
+                Err(Error::NoPath { source, sink }) => return Err(Error::NoSegmentPath { from, to, source, sink }),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut out: Vec<Bathymetry> = vec![];
+        for (_, segment) in visited {
+            // each segment starts where the one before it ended
+            let joint = usize::from(!out.is_empty() && out.last() == segment.first());
+            out.extend(segment.into_iter().skip(joint));
+        }
+        Ok(out)
     }
 
     pub fn from_path(&self, points: &[Point]) -> Vec<Bathymetry> {
         let mut out = vec![];
-        for point in points {
-            if let Some(elem) = self.points.nearest_neighbor(point) {
-                out.push(elem.clone());
+        for &point in points {
+            if let Some(interpolated) = self.interpolated(point) {
+                out.push(interpolated);
+            } else if let Some(id) = self.points.nearest(&point) {
+                out.push(self.points.get(id).clone());
             }
         }
         out
@@ -134,12 +452,16 @@ impl ThalwegGenerator {
             let dist = f64::min(dist_1, dist_2);
             // avoid overlapping with neighbors
             let resolution = f64::min(dist / 2.0, self.resolution as f64);
-            // RTree uses distance^2 in locate_within_distance
-            let distance_squared = resolution * resolution;
-
             let best_neighbor = self
                 .points
-                .locate_within_distance(current.point(), distance_squared)
+                .within(&current.point(), resolution)
+                .map(|id| self.points.get(id))
+                // never jump the shoreline or drag either adjacent segment across land
+                .filter(|neighbor| {
+                    !self.crosses_land(current.point(), neighbor.point())
+                        && !self.crosses_land(prev.point(), neighbor.point())
+                        && !self.crosses_land(neighbor.point(), next.point())
+                })
                 .fold(current, |best, neighbor| {
                     if best.depth() < neighbor.depth() {
                         neighbor
@@ -178,27 +500,155 @@ impl ThalwegGenerator {
         out
     }
 
+    /// Sample the path every `resolution` metres along it, snapping to soundings or interpolating at each
     pub fn populate(&self, points: &[Bathymetry]) -> Vec<Bathymetry> {
         let mut out = vec![];
-        let path: LineString<f64> = points.iter().map(|p| p.point()).collect();
-        let length = path.geodesic_length();
-        let resolution = (self.resolution as f64) * 0.00001;
-        let num_chunks = (length / resolution).ceil() as usize;
-        for chunk in 0..=num_chunks {
-            let distance_from_start = chunk * self.resolution;
-            let fraction = (distance_from_start as f64) / length;
-            if let Some(point) = path
-                .line_interpolate_point(fraction)
-                .and_then(|p| self.points.nearest_neighbor(&p.x_y()))
-            {
-                if out.last().map_or(true, |end| end != point) {
-                    out.push(point.clone());
+        for Station { point: station, .. } in stations(points, self.resolution as f64) {
+            if let Some(point) = self.interpolated(station).or_else(|| self.nearest_on_same_side(station).cloned()) {
+                if out.last() != Some(&point) {
+                    out.push(point);
+                }
+            }
+        }
+        out
+    }
+
+    /// Points exactly `spacing` metres apart along the path, keeping its first and final points, with the depth
+    /// at each found by the chosen interpolation, or taken from the nearest sounding when snapping or where there is
+    /// nothing to interpolate between.
+    ///
+    /// The spacing must be positive and finite, and leave no more than a million points along the path, and no point
+    /// may fall on land or where no sounding can be reached from it.
+    pub fn resample(&self, points: &[Bathymetry], spacing: f64) -> Result<Vec<Bathymetry>, Error> {
+        if !spacing.is_finite() || spacing <= 0.0 {
+            return Err(Error::InvalidInput(format!("spacing of {} m is not a positive distance", spacing)));
+        }
+        let length: f64 = points.windows(2).map(|pair| pair[0].distance_to(&pair[1])).sum();
+        if length / spacing > MAX_STATIONS {
+            return Err(Error::InvalidInput(format!(
+                "spacing of {} m is too small for a path {:.0} m long",
+                spacing, length
+            )));
+        }
+        let stations: Vec<Point> = stations(points, spacing).into_iter().map(|station| station.point).collect();
+        let last = stations.len().saturating_sub(1);
+        stations
+            .into_iter()
+            .enumerate()
+            .map(|(index, station)| match index {
+                0 => Ok(points[0].clone()),
+                _ if index == last => Ok(points[points.len() - 1].clone()),
+                _ => self
+                    .depth_at(station)
+                    // the nearest sounding still gives a depth without moving the point onto it
+                    .or_else(|| self.nearest_on_same_side(station).map(Bathymetry::depth))
+                    .map(|depth| Bathymetry::new(station.1, station.0, depth))
+                    .ok_or_else(|| {
+                        Error::InvalidInput(format!(
+                            "no depth for the point {:.0} m along the path at {:.6}, {:.6}: on land or out of reach of the data",
+                            index as f64 * spacing,
+                            station.1,
+                            station.0
+                        ))
+                    }),
+            })
+            .collect()
+    }
+
+    /// Id of the point to start or end a thalweg at for `point`: the nearest that can be reached without crossing
+    /// land, or simply the nearest where `point` is itself on land or can reach none
+    fn nearest_to_end(&self, point: Point) -> Option<usize> {
+        match &self.land {
+            Some(land) if !land.contains(point) => self
+                .points
+                .nearest_where(&point, &|candidate| !land.crosses(point, candidate.point()))
+                .or_else(|| self.points.nearest(&point)),
+            _ => self.points.nearest(&point),
+        }
+    }
+
+    /// Nearest point that can be reached from `point` without crossing land
+    fn nearest_on_same_side(&self, point: Point) -> Option<&Bathymetry> {
+        match &self.land {
+            Some(land) if land.contains(point) => None,
+            Some(land) => self
+                .points
+                .nearest_where(&point, &|candidate| !land.crosses(point, candidate.point()))
+                .map(|id| self.points.get(id)),
+            None => self.points.nearest(&point).map(|id| self.points.get(id)),
+        }
+    }
+
+    /// Smoothed path with depths found at the new positions as by `resample`, keeping its first and final points.
+    /// Water is taken to be wherever a sounding is close by, less any land, and wherever the smoothed line would
+    /// leave it the original path is followed instead
+    pub fn smooth(&self, points: &[Bathymetry], smoothing: Smoothing) -> Vec<Bathymetry> {
+        let path: Vec<Point> = points.iter().map(Bathymetry::point).collect();
+        // as for the centreline, far enough to reach a sounding from anywhere between them
+        let reach = 1.5 * centreline::spacing(self.points.as_ref()).unwrap_or(0.0);
+        let in_water = |point: Point| {
+            !self.land.as_ref().is_some_and(|land| land.contains(point)) && self.points.within(&point, reach).next().is_some()
+        };
+
+        // each point paired with its index when it is one of the original points
+        let mut out: Vec<(Bathymetry, Option<usize>)> = vec![];
+        let last = (points.len() as f64 - 1.0).max(0.0);
+        // index of the first original point not yet passed
+        let mut next_original = 0;
+        for (point, position) in smooth::smooth(&path, smoothing) {
+            let end = position == 0.0 || position == last;
+            let joins = !out.last().is_some_and(|(previous, _)| self.crosses_land(previous.point(), point));
+            if !end && joins && in_water(point) {
+                if let Some(depth) = self.depth_at(point) {
+                    out.push((Bathymetry::new(point.1, point.0, depth), None));
+                    next_original = next_original.max(position.floor() as usize + 1);
+                    continue;
+                }
+            }
+            while next_original < points.len() && next_original as f64 <= position.ceil() {
+                let original = points[next_original].point();
+                // smoothed points that cannot reach the original path without crossing land are dropped, taking
+                // it up again from the last original point kept, as the original path itself keeps off land
+                while out
+                    .last()
+                    .is_some_and(|(previous, index)| index.is_none() && self.crosses_land(previous.point(), original))
+                {
+                    out.pop();
                 }
+                let resume = match out.last() {
+                    Some((_, Some(index))) => index + 1,
+                    Some((_, None)) => next_original,
+                    None => 0,
+                };
+                out.extend((resume..=next_original).map(|index| (points[index].clone(), Some(index))));
+                next_original += 1;
             }
         }
+        let mut out: Vec<Bathymetry> = out.into_iter().map(|(point, _)| point).collect();
+        out.dedup();
         out
     }
 
+    /// Sounding at exactly `point` with its depth interpolated from those around it, unless snapping to the nearest
+    /// sounding, the point is on land, or there is nothing to interpolate between
+    fn interpolated(&self, point: Point) -> Option<Bathymetry> {
+        if self.interpolation == Interpolation::Nearest {
+            return None;
+        }
+        let depth = self.depth_at(point)?;
+        Some(Bathymetry::new(point.1, point.0, depth))
+    }
+
+    /// Depth at exactly `point` by the chosen interpolation, from soundings on the same side of any land
+    fn depth_at(&self, point: Point) -> Option<f64> {
+        if self.land.as_ref().is_some_and(|land| land.contains(point)) {
+            return None;
+        }
+        let usable = |candidate: &Bathymetry| !self.crosses_land(point, candidate.point());
+        let reach = self.interpolation_reach.unwrap_or(self.resolution as f64);
+        interpolate::depth_at(self.points.as_ref(), point, self.interpolation, reach, &usable)
+    }
+
     pub fn add_midpoints(&self, points: &[Bathymetry]) -> Vec<Bathymetry> {
         let mut out = vec![];
         for window in points.windows(2) {
@@ -207,11 +657,11 @@ impl ThalwegGenerator {
             let line = Line::new(a.point(), b.point());
 
             out.push(a);
-            if let Some(point) = line
-                .line_interpolate_point(0.5)
-                .and_then(|p| self.points.nearest_neighbor(&p.x_y()))
-            {
-                out.push(point.clone());
+            if let Some(point) = line.line_interpolate_point(0.5).and_then(|p| {
+                let p = p.x_y();
+                self.interpolated(p).or_else(|| self.points.nearest(&p).map(|id| self.points.get(id).clone()))
+            }) {
+                out.push(point);
             }
             out.push(b);
             out.dedup();
@@ -219,18 +669,99 @@ impl ThalwegGenerator {
         out
     }
 
-    pub fn simplify(&self, points: &[Bathymetry]) -> Vec<Bathymetry> {
-        let epsilon = 1.0 / 3600.0 / 5.0;
-        let path: LineString<f64> = points.iter().map(|p| p.point()).collect();
-        let new_path = path.simplifyvw_preserve(&epsilon);
-        let path_vec: Vec<(f64, f64)> = new_path.coords().map(|c| c.x_y()).collect();
-        self.from_path(&path_vec)
+    /// Remove points that stray less than the tolerance from the rest of the path, see `simplify::simplify`,
+    /// never removing its ends or any sill or basin at least `min_prominence` metres high or deep along it
+    pub fn simplify(
+        &self,
+        points: &[Bathymetry],
+        simplification: Simplification,
+        min_prominence: f64,
+    ) -> Vec<Bathymetry> {
+        let section = section::section(points);
+        let features: Vec<usize> = analysis::features(&section, min_prominence)
+            .iter()
+            .filter_map(|feature| {
+                section
+                    .iter()
+                    .position(|s| s.distance() == feature.distance && s.point() == feature.point)
+            })
+            .collect();
+        let path: Vec<Point> = points.iter().map(Bathymetry::point).collect();
+        simplify::simplify(&path, simplification, &features)
+            .into_iter()
+            .map(|index| points[index].clone())
+            .collect()
+    }
+
+    /// Sample transects of `half_width` metres either side of the path at stations `spacing` metres apart along it,
+    /// placed as by `resample`, taking depths every `resolution` metres across.
+    ///
+    /// Both distances must be positive and finite, and leave no more than ten million samples in all.
+    pub fn cross_sections(&self, path: &[Bathymetry], spacing: f64, half_width: f64) -> Result<Vec<CrossSection>, Error> {
+        cross_section::cross_sections(path, self.points.as_ref(), spacing, half_width, self.resolution as f64)
+    }
+}
+
+/// Position along a path, with its distance from the start and the bearing of the segment it lies on
+pub(crate) struct Station {
+    pub(crate) point: Point,
+    pub(crate) distance: f64,
+    pub(crate) bearing: f64,
+}
+
+/// Positions every `spacing` metres of great-circle distance along the path, ending with its final point,
+/// or the points of the path themselves when there is no spacing
+pub(crate) fn stations(points: &[Bathymetry], spacing: f64) -> Vec<Station> {
+    let mut out = vec![];
+    let mut start_of_segment = 0.0;
+    let mut next_station = 0.0;
+    let mut bearing = 0.0;
+    for window in points.windows(2) {
+        let length = window[0].distance_to(&window[1]);
+        let end_of_segment = start_of_segment + length;
+        let start = geo::Point::from(window[0].point());
+        if length > 0.0 {
+            bearing = start.bearing(geo::Point::from(window[1].point()));
+        }
+        if spacing <= 0.0 {
+            out.push(Station {
+                point: window[0].point(),
+                distance: start_of_segment,
+                bearing,
+            });
+        } else if length > 0.0 {
+            while next_station < end_of_segment {
+                out.push(Station {
+                    point: start.haversine_destination(bearing, next_station - start_of_segment).x_y(),
+                    distance: next_station,
+                    bearing,
+                });
+                next_station += spacing;
+            }
+        }
+        start_of_segment = end_of_segment;
+    }
+    if let Some(last) = points.last() {
+        // a station a hair short of the end would only duplicate it
+        if spacing > 0.0 && out.last().is_some_and(|station| last.distance_to_point(&station.point) < spacing / 1000.0) {
+            out.pop();
+        }
+        out.push(Station {
+            point: last.point(),
+            distance: start_of_segment,
+            bearing,
+        });
     }
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::index::TreeIndex;
+    use crate::network;
+
+    use geo::LineString;
 
     #[test]
     fn thalweg_provides_nodes_containing_source_and_sink() {
@@ -240,7 +771,7 @@ mod tests {
         ];
         let generator = ThalwegGenerator::new(data.clone(), 400, false);
         let path = generator.thalweg(data.first().unwrap().point(), data.last().unwrap().point());
-        assert_eq!(path, Some(data));
+        assert_eq!(path.ok(), Some(data));
     }
 
     #[test]
@@ -259,7 +790,7 @@ mod tests {
             expected.first().unwrap().point(),
             expected.last().unwrap().point(),
         );
-        assert_eq!(path, Some(expected));
+        assert_eq!(path.ok(), Some(expected));
     }
 
     #[test]
@@ -288,7 +819,7 @@ mod tests {
             expected.first().unwrap().point(),
             expected.last().unwrap().point(),
         );
-        assert_eq!(path, Some(expected));
+        assert_eq!(path.ok(), Some(expected));
     }
 
     #[test]
@@ -312,7 +843,193 @@ mod tests {
             expected.first().unwrap().point(),
             expected.last().unwrap().point(),
         );
-        assert_eq!(path, Some(expected));
+        assert_eq!(path.ok(), Some(expected));
+    }
+
+    #[test]
+    fn thalweg_reports_when_no_path_exists() {
+        let data = vec![
+            Bathymetry::new(0.0, 0.0, 0.0),
+            Bathymetry::new(1.0, 1.0, 0.0),
+        ];
+        let generator = ThalwegGenerator::new(data.clone(), 400, false);
+        let path = generator.thalweg(data[0].point(), data[1].point());
+        assert!(matches!(path, Err(Error::NoPath { .. })));
+    }
+
+    #[test]
+    fn thalweg_through_does_not_repeat_joints() {
+        let data: Vec<Bathymetry> = (0..5).map(|i| Bathymetry::new(0.001 * i as f64, 0.0, 10.0)).collect();
+        let generator = ThalwegGenerator::new(data.clone(), 150, false);
+        let waypoints = [data[0].point(), data[2].point(), data[2].point(), data[4].point()];
+        assert_eq!(generator.thalweg_through(&waypoints).ok(), Some(data.clone()));
+        assert_eq!(generator.thalweg_through(&waypoints[1..2]).ok(), Some(vec![data[2].clone()]));
+        assert_eq!(generator.thalweg_through(&[]).ok(), Some(vec![]));
+    }
+
+    #[test]
+    fn thalweg_via_drops_unreachable_optional_waypoints() {
+        let mut data: Vec<Bathymetry> = (0..5).map(|i| Bathymetry::new(0.001 * i as f64, 0.0, 10.0)).collect();
+        // too far from the channel to reach
+        let island = Bathymetry::new(0.002, 0.01, 10.0);
+        data.push(island.clone());
+        let generator = ThalwegGenerator::new(data.clone(), 150, false);
+        let channel = data[..5].to_vec();
+
+        let waypoints = [
+            Waypoint::mandatory(data[0].point()),
+            Waypoint::optional(island.point()),
+            Waypoint::optional(data[3].point()),
+            Waypoint::mandatory(data[4].point()),
+        ];
+        assert_eq!(generator.thalweg_via(&waypoints).ok(), Some(channel.clone()));
+
+        let result = generator.thalweg_via(&[
+            Waypoint::mandatory(data[0].point()),
+            Waypoint::mandatory(data[2].point()),
+            Waypoint::mandatory(island.point()),
+            Waypoint::mandatory(data[4].point()),
+        ]);
+        assert!(matches!(result, Err(Error::NoSegmentPath { from: 1, to: 2, .. })));
+    }
+
+    #[test]
+    fn network_branches_share_their_trunk() {
+        // trunk running north from the mouth, forking into arms to the north east and north west
+        let mut data: Vec<Bathymetry> = (0..4).map(|i| Bathymetry::new(0.001 * i as f64, 0.0, 10.0)).collect();
+        for i in 1..3 {
+            data.push(Bathymetry::new(0.003 + 0.001 * i as f64, 0.001 * i as f64, 10.0));
+            data.push(Bathymetry::new(0.003 + 0.001 * i as f64, -0.001 * i as f64, 10.0));
+        }
+        let generator = ThalwegGenerator::new(data.clone(), 200, false);
+        let heads = [data[6].point(), data[7].point(), data[2].point()];
+        let branches = generator.network(data[0].point(), &heads).unwrap();
+
+        assert_eq!(branches.len(), 3);
+        assert_eq!(branches[0].parent, None);
+        assert_eq!(branches[0].start, 0);
+        let trunk: Vec<Bathymetry> = [0, 1, 2, 3, 4, 6].iter().map(|&i| data[i].clone()).collect();
+        assert_eq!(branches[0].path, trunk);
+        assert_eq!(branches[1].parent, Some(0));
+        assert_eq!(branches[1].path, vec![data[3].clone(), data[5].clone(), data[7].clone()]);
+        let junction = section::section(&trunk)[3].distance();
+        assert_eq!(branches[1].start, junction);
+        assert_eq!(branches[1].section()[0].distance(), junction);
+        // a head on an earlier branch is a branch of one point
+        assert_eq!(branches[2].parent, Some(0));
+        assert_eq!(branches[2].path, vec![data[2].clone()]);
+    }
+
+    #[test]
+    fn densified_network_keeps_its_junctions() {
+        let mut data: Vec<Bathymetry> = (0..4).map(|i| Bathymetry::new(0.001 * i as f64, 0.0, 10.0)).collect();
+        for i in 1..3 {
+            data.push(Bathymetry::new(0.003 + 0.001 * i as f64, 0.001 * i as f64, 10.0));
+            data.push(Bathymetry::new(0.003 + 0.001 * i as f64, -0.001 * i as f64, 10.0));
+        }
+        let generator = ThalwegGenerator::new(data.clone(), 200, false);
+        let heads = [data[6].point(), data[7].point(), data[2].point()];
+        let branches = generator.network(data[0].point(), &heads).unwrap();
+
+        let populated = network::densify(&branches, |path| Ok(generator.populate(path))).unwrap();
+        let resampled = network::densify(&branches, |path| generator.resample(path, 70.0)).unwrap();
+        assert!(resampled[0].path.len() > branches[0].path.len());
+        for densified in [populated, resampled] {
+            let trunk = &densified[0];
+            assert_eq!(trunk.path.first(), Some(&data[0]));
+            assert_eq!(trunk.path.last(), Some(&data[6]));
+            for branch in &densified[1..] {
+                // each branch still leaves the trunk where it did, now measured along the densified trunk
+                let junction = trunk.path.iter().position(|point| Some(point) == branch.path.first()).unwrap();
+                assert_eq!(branch.start, trunk.section()[junction].distance());
+            }
+            assert_eq!(densified[1].path.last(), Some(&data[7]));
+            assert_eq!(densified[2].path, vec![data[2].clone()]);
+        }
+    }
+
+    #[test]
+    fn network_reports_unreachable_heads() {
+        let data = vec![Bathymetry::new(0.0, 0.0, 10.0), Bathymetry::new(1.0, 1.0, 10.0)];
+        let generator = ThalwegGenerator::new(data.clone(), 200, false);
+        let result = generator.network(data[0].point(), &[data[1].point()]);
+        assert!(matches!(result, Err(Error::NoPath { .. })));
+    }
+
+    #[test]
+    fn flow_method_needs_gridded_bathymetry() {
+        let grid: Vec<Bathymetry> = (0..3)
+            .flat_map(|row| (0..4).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let depth = if row == 1 { 20.0 } else { 5.0 };
+                Bathymetry::new(0.001 * row as f64, 0.001 * column as f64, depth)
+            })
+            .collect();
+        let generator = ThalwegGenerator::new(grid.clone(), 200, false).with_method(Method::Flow);
+        let path = generator.thalweg((0.0, 0.001), (0.003, 0.001)).unwrap();
+        let channel: Vec<Bathymetry> = grid[4..8].to_vec();
+        assert_eq!(path, channel);
+
+        let scattered = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.0013, 0.0007, 10.0),
+            Bathymetry::new(0.0021, 0.0031, 10.0),
+        ];
+        let generator = ThalwegGenerator::new(scattered, 200, false).with_method(Method::Flow);
+        assert!(matches!(generator.thalweg((0.0, 0.0), (0.0031, 0.0021)), Err(Error::InvalidInput(_))));
+        assert_eq!("Flow".parse(), Ok(Method::Flow));
+        assert_eq!(Method::Search.to_string().parse(), Ok(Method::Search));
+    }
+
+    #[test]
+    fn flow_method_follows_the_channel_of_maximum_accumulation() {
+        // bank along the first row, falling towards the sink faster than it falls into the channel beside it
+        let depths = [
+            [5.0, 10.0, 15.0, 20.0, 25.0, 30.0],
+            [6.0, 12.0, 18.0, 24.0, 30.0, 36.0],
+            [1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        ];
+        let grid: Vec<Bathymetry> = (0..3)
+            .flat_map(|row| (0..6).map(move |column| (row, column)))
+            .map(|(row, column)| Bathymetry::new(0.001 * row as f64, 0.001 * column as f64, depths[row][column]))
+            .collect();
+        let generator = ThalwegGenerator::new(grid.clone(), 200, false).with_method(Method::Flow);
+        let path = generator.thalweg((0.0, 0.0), (0.005, 0.001)).unwrap();
+        assert_eq!(path, grid[6..12].to_vec());
+        // water from the source itself runs down the bank before joining the channel
+        let descent = generator.flow_routing(11).unwrap().path(0).unwrap();
+        assert_eq!(descent, vec![0, 1, 8, 9, 10, 11]);
+
+        // with no channel within reach, the flow path starts from the source
+        let generator = ThalwegGenerator::new(grid.clone(), 50, false).with_method(Method::Flow);
+        let path = generator.thalweg((0.0, 0.0), (0.005, 0.001)).unwrap();
+        let expected: Vec<Bathymetry> = descent.iter().map(|&id| grid[id].clone()).collect();
+        assert_eq!(path, expected);
+    }
+
+    #[test]
+    fn centre_method_snaps_the_centreline_to_soundings() {
+        // deepest soundings zigzag across a channel seven wide
+        let mut data = vec![];
+        for x in 0..=30 {
+            for y in 0..=6 {
+                let depth = if y == [1, 5][x % 2] { 50.0 } else { 10.0 };
+                data.push(Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, depth));
+            }
+        }
+        let generator = ThalwegGenerator::new(data, 150, false).with_method(Method::Centre);
+        let path = generator.thalweg((0.0, 0.003), (0.03, 0.003)).unwrap();
+        assert_eq!(path.len(), 31);
+        assert!(path.iter().all(|point| point.point().1 == 0.003));
+        assert!(path.windows(2).all(|pair| pair[0] != pair[1]));
+        assert_eq!("center".parse(), Ok(Method::Centre));
+    }
+
+    #[test]
+    fn thalweg_reports_empty_dataset() {
+        let generator = ThalwegGenerator::new(vec![], 400, false);
+        let path = generator.thalweg((0.0, 0.0), (1.0, 1.0));
+        assert!(matches!(path, Err(Error::EmptyDataset)));
     }
 
     #[test]
@@ -532,4 +1249,375 @@ mod tests {
         let path = generator.from_path(&input);
         assert_eq!(path, expected);
     }
+
+    #[test]
+    fn resample_spaces_points_exactly() {
+        // along a parallel far enough north for degrees of longitude to be much shorter than those of latitude
+        let path = vec![
+            Bathymetry::new(60.0, 0.0, 10.0),
+            Bathymetry::new(60.0, 0.02, 20.0),
+            Bathymetry::new(60.01, 0.02, 30.0),
+        ];
+        let data: Vec<Bathymetry> = (0..=20)
+            .flat_map(|x| (0..=10).map(move |y| Bathymetry::new(60.0 + y as f64 * 0.001, x as f64 * 0.001, 5.0)))
+            .collect();
+        let generator = ThalwegGenerator::new(data, 100, false);
+        let resampled = generator.resample(&path, 250.0).unwrap();
+        assert_eq!(resampled.first(), path.first());
+        assert_eq!(resampled.last(), path.last());
+        // 1112m along the parallel then 1112m along the meridian
+        assert_eq!(resampled.len(), 10);
+        for window in resampled[..4].windows(2).chain(resampled[5..9].windows(2)) {
+            assert!((window[0].distance_to(&window[1]) - 250.0).abs() < 0.2);
+        }
+        assert!(resampled[1..9].iter().all(|point| point.depth() == 5.0));
+        assert!(generator.resample(&path[..1], 250.0).unwrap() == path[..1]);
+    }
+
+    #[test]
+    fn resample_takes_nearest_depth_out_of_reach_of_interpolation() {
+        // soundings about 111 m apart, so the middle of each cell is out of reach of the interpolation
+        let data: Vec<Bathymetry> = (0..=4)
+            .flat_map(|x| (0..=4).map(move |y| Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, 10.0 + x as f64)))
+            .collect();
+        let path = vec![Bathymetry::new(0.0015, 0.0, 10.0), Bathymetry::new(0.0015, 0.004, 14.0)];
+        let generator = ThalwegGenerator::new(data, 50, false)
+            .with_interpolation(Interpolation::Triangulated)
+            .unwrap();
+        let resampled = generator.resample(&path, 100.0).unwrap();
+        assert_eq!(resampled.len(), 6);
+        for point in &resampled[1..5] {
+            let nearest = generator.points.get(generator.points.nearest(&point.point()).unwrap());
+            assert_eq!(point.depth(), nearest.depth());
+            assert_eq!(point.point().1, 0.0015);
+        }
+
+        let generator = generator.with_land_mask(land((0.0015, 0.001), (0.0022, 0.002)));
+        let error = generator.resample(&path, 100.0).unwrap_err().to_string();
+        assert!(error.contains("200 m along the path"), "{}", error);
+    }
+
+    #[test]
+    fn resample_rejects_unusable_spacing() {
+        let path = vec![Bathymetry::new(0.0, 0.0, 10.0), Bathymetry::new(0.0, 0.01, 10.0)];
+        let generator = ThalwegGenerator::new(path.clone(), 100, false);
+        for spacing in [0.0, -250.0, f64::NAN, f64::INFINITY, 1e-6] {
+            assert!(matches!(generator.resample(&path, spacing), Err(Error::InvalidInput(_))), "{}", spacing);
+        }
+        assert!(generator.resample(&path, 0.01).is_ok());
+    }
+
+    #[test]
+    fn simplify_keeps_sills_and_basins() {
+        // straight line with a sill between two basins
+        let depths = [10.0, 10.0, 40.0, 10.0, 10.0, 3.0, 30.0, 25.0, 10.0];
+        let path: Vec<Bathymetry> = depths
+            .iter()
+            .enumerate()
+            .map(|(x, &depth)| Bathymetry::new(48.0, x as f64 * 0.0001, depth))
+            .collect();
+        let generator = ThalwegGenerator::new(path.clone(), 50, false);
+        for simplification in [Simplification::DouglasPeucker(10.0), Simplification::Visvalingam(10.0)] {
+            let simplified = generator.simplify(&path, simplification, 10.0);
+            let kept: Vec<Bathymetry> = [0, 2, 5, 6, 8].iter().map(|&index| path[index].clone()).collect();
+            assert_eq!(simplified, kept);
+            let simplified = generator.simplify(&path, simplification, 50.0);
+            assert_eq!(simplified, vec![path[0].clone(), path[8].clone()]);
+        }
+    }
+
+    #[test]
+    fn smooth_does_not_cut_across_land() {
+        // water in an L along the south and west of a block of land, with the path turning the corner
+        let land = land((0.0035, 0.0035), (0.03, 0.03));
+        let data: Vec<Bathymetry> = (0..=20)
+            .flat_map(|x| (0..=20).map(move |y| Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, 10.0 + x as f64)))
+            .filter(|point| !land.contains(point.point()))
+            .collect();
+        let mut path: Vec<Bathymetry> = (1..=20).rev().map(|x| Bathymetry::new(0.001, x as f64 * 0.001, 20.0)).collect();
+        path.extend((2..=20).map(|y| Bathymetry::new(y as f64 * 0.001, 0.001, 20.0)));
+        let generator = ThalwegGenerator::new(data, 100, false).with_land_mask(land);
+        // only the widest window would reach the land, keeping to the original path around the corner instead
+        for (smoothing, rounded) in [
+            (Smoothing::Window(3000.0), false),
+            (Smoothing::Window(500.0), true),
+            (Smoothing::Chaikin(4), true),
+            (Smoothing::BSpline(4), true),
+        ] {
+            let smoothed = generator.smooth(&path, smoothing);
+            assert_eq!(smoothed.first(), path.first());
+            assert_eq!(smoothed.last(), path.last());
+            assert!(smoothed.windows(2).all(|pair| !generator.crosses_land(pair[0].point(), pair[1].point())));
+            let corner = smoothed.iter().any(|point| point.point() == (0.001, 0.001));
+            assert_eq!(corner, !rounded, "{}", smoothing);
+        }
+    }
+
+    #[test]
+    fn smooth_keeps_every_segment_off_land() {
+        // an island just inside the corner of an L-shaped path, which smoothing pulls the line past
+        let island = land((0.00125, 0.00275), (0.00155, 0.00305));
+        let data: Vec<Bathymetry> = (0..=20)
+            .flat_map(|x| (0..=20).map(move |y| Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, 10.0 + x as f64)))
+            .collect();
+        let mut path: Vec<Bathymetry> = (1..=20).rev().map(|x| Bathymetry::new(0.001, x as f64 * 0.001, 20.0)).collect();
+        path.extend((2..=20).map(|y| Bathymetry::new(y as f64 * 0.001, 0.001, 20.0)));
+        let generator = ThalwegGenerator::new(data, 100, false).with_land_mask(island);
+        let smoothed = generator.smooth(&path, Smoothing::Window(1000.0));
+        for pair in smoothed.windows(2) {
+            assert!(!generator.crosses_land(pair[0].point(), pair[1].point()), "{:?}", pair);
+        }
+        // the line still leaves the original path wherever it can
+        assert!(smoothed.iter().filter(|point| !path.contains(point)).count() > 10);
+    }
+
+    #[test]
+    fn add_midpoints_can_interpolate() {
+        let one_second = 1.0 / 3600.0;
+        let data = vec![
+            Bathymetry::new(-1.0 * one_second, -1.0 * one_second, 140.0),
+            Bathymetry::new(-1.0 * one_second, 0.0 * one_second, 150.0),
+            Bathymetry::new(0.0 * one_second, -1.0 * one_second, 100.0),
+            Bathymetry::new(0.0 * one_second, 0.0 * one_second, 9.0),
+            Bathymetry::new(0.0 * one_second, 1.0 * one_second, 140.0),
+        ];
+        let input = vec![data[0].clone(), data[4].clone()];
+        let generator = ThalwegGenerator::new(data.clone(), 50, false)
+            .with_interpolation(Interpolation::Bilinear)
+            .unwrap();
+        let path = generator.add_midpoints(&input);
+        // halfway between the first and second rows, where only two of the four grid points have data
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[1].point().0, 0.0);
+        assert!((path[1].point().1 + 0.5 * one_second).abs() < 1e-6);
+        assert_eq!(path[1].depth(), 79.5);
+
+        let scattered = ThalwegGenerator::from_index(Box::new(TreeIndex::new(data)), 50, false);
+        assert!(scattered.with_interpolation(Interpolation::Bilinear).is_err());
+    }
+
+    #[test]
+    fn interpolation_reach_is_separate_from_resolution() {
+        // soundings about 111 m apart, so the middle of a cell is further than the resolution from any of them
+        let data: Vec<Bathymetry> = (0..=4)
+            .flat_map(|x| (0..=4).map(move |y| Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, 10.0 + x as f64)))
+            .collect();
+        let middle = (0.0015, 0.0015);
+        let generator = ThalwegGenerator::new(data, 50, false)
+            .with_interpolation(Interpolation::Triangulated)
+            .unwrap();
+        assert_eq!(generator.depth_at(middle), None);
+        let generator = generator.with_interpolation_reach(500.0);
+        assert!((generator.depth_at(middle).unwrap() - 11.5).abs() < 1e-6);
+    }
+
+    fn land(min: Point, max: Point) -> LandMask {
+        let (x0, y0) = min;
+        let (x1, y1) = max;
+        let rect = geo::Polygon::new(
+            LineString::from(vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1), (x0, y0)]),
+            vec![],
+        );
+        LandMask::new(geo::MultiPolygon(vec![rect]))
+    }
+
+    #[test]
+    fn thalweg_ends_on_the_same_side_of_land() {
+        // the sounding nearest the source lies across a strip of land from it
+        let data = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.0, 0.002, 10.0),
+            Bathymetry::new(0.0006, 0.0, 50.0),
+        ];
+        let generator = ThalwegGenerator::new(data.clone(), 250, false)
+            .with_land_mask(land((-1.0, 0.0004), (1.0, 0.0005)));
+        let path = generator.thalweg((0.0, 0.00035), (0.002, 0.0)).unwrap();
+        assert_eq!(path, vec![data[0].clone(), data[1].clone()]);
+    }
+
+    #[test]
+    fn thalweg_goes_around_land() {
+        let data = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.001, 0.001, 10.0),
+            Bathymetry::new(0.0, 0.002, 10.0),
+        ];
+        let source = data[0].point();
+        let sink = data[2].point();
+
+        let generator = ThalwegGenerator::new(data.clone(), 250, false);
+        assert_eq!(generator.thalweg(source, sink).ok(), Some(vec![data[0].clone(), data[2].clone()]));
+
+        let generator = ThalwegGenerator::new(data.clone(), 250, false)
+            .with_land_mask(land((0.0009, -0.0002), (0.0011, 0.0002)));
+        assert_eq!(generator.thalweg(source, sink).ok(), Some(data));
+    }
+
+    #[test]
+    fn thalweg_reports_when_land_blocks_path() {
+        let data = vec![Bathymetry::new(0.0, 0.0, 10.0), Bathymetry::new(0.0, 0.002, 10.0)];
+        let generator = ThalwegGenerator::new(data.clone(), 250, false)
+            .with_land_mask(land((0.0009, -0.0002), (0.0011, 0.0002)));
+        let path = generator.thalweg(data[0].point(), data[1].point());
+        assert!(matches!(path, Err(Error::NoPath { .. })));
+    }
+
+    #[test]
+    fn sink_does_not_cross_shoreline() {
+        let data = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.0003, 0.005, 10.0),
+            Bathymetry::new(-0.0003, 0.005, 100.0),
+            Bathymetry::new(0.0, 0.01, 10.0),
+        ];
+        let input = vec![data[0].clone(), data[1].clone(), data[3].clone()];
+
+        let generator = ThalwegGenerator::new(data.clone(), 100, false);
+        assert_eq!(generator.sink(&input)[1], data[2]);
+
+        let generator = ThalwegGenerator::new(data.clone(), 100, false)
+            .with_land_mask(land((0.004, -0.0001), (0.006, 0.0001)));
+        assert_eq!(generator.sink(&input), input);
+    }
+
+    #[test]
+    fn populate_does_not_snap_across_shoreline() {
+        let data = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.00025, 0.001, 50.0),
+            Bathymetry::new(-0.0004, 0.001, 10.0),
+            Bathymetry::new(0.0, 0.002, 10.0),
+        ];
+        let input = vec![data[0].clone(), data[3].clone()];
+
+        let generator = ThalwegGenerator::new(data.clone(), 100, false);
+        assert_eq!(generator.populate(&input), vec![data[0].clone(), data[1].clone(), data[3].clone()]);
+
+        // the deep point is closest to the path, but on the far side of a spit
+        let generator = ThalwegGenerator::new(data.clone(), 100, false)
+            .with_land_mask(land((0.0008, 0.0001), (0.0012, 0.0002)));
+        assert_eq!(generator.populate(&input), vec![data[0].clone(), data[2].clone(), data[3].clone()]);
+    }
+
+    #[test]
+    fn thalweg_uses_cost_function() {
+        let data = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.0, 0.001, 60.0),
+            Bathymetry::new(0.001, 0.001, 10.0),
+            Bathymetry::new(0.0, 0.002, 10.0),
+        ];
+        let source = data[0].point();
+        let sink = data[3].point();
+
+        let generator = ThalwegGenerator::new(data.clone(), 160, false).with_cost_function(Cost::Distance.build(60.0));
+        let path = generator.thalweg(source, sink);
+        assert_eq!(path.ok(), Some(vec![data[0].clone(), data[1].clone(), data[3].clone()]));
+
+        // the straight route drops into and climbs out of a hole
+        let generator = ThalwegGenerator::new(data.clone(), 160, false).with_cost_function(Cost::Slope(10.0).build(60.0));
+        let path = generator.thalweg(source, sink);
+        assert_eq!(path.ok(), Some(vec![data[0].clone(), data[2].clone(), data[3].clone()]));
+    }
+
+    // n by n grid about 55 m apart with an uneven, deterministic seabed
+    fn synthetic_grid(n: usize, seed: usize) -> Vec<Bathymetry> {
+        let mut out = vec![];
+        for i in 0..n {
+            for j in 0..n {
+                let depth = ((i * 7 + j * 13 + seed) % 11) as f64 * 10.0 + 5.0;
+                out.push(Bathymetry::new(i as f64 * 0.0005, j as f64 * 0.0005, depth));
+            }
+        }
+        out
+    }
+
+    // exhaustive Dijkstra to compare against
+    fn minimum_cost(data: &[Bathymetry], resolution: f64, cost: &dyn CostFunction, source: usize, sink: usize) -> f64 {
+        let mut best = vec![f64::INFINITY; data.len()];
+        let mut done = vec![false; data.len()];
+        best[source] = 0.0;
+        while let Some(current) = (0..data.len())
+            .filter(|&i| !done[i] && best[i].is_finite())
+            .min_by(|&a, &b| best[a].total_cmp(&best[b]))
+        {
+            done[current] = true;
+            for next in 0..data.len() {
+                let length = data[current].distance_to(&data[next]);
+                if next != current && length <= resolution {
+                    let candidate = best[current] + cost.cost(&data[current], &data[next], length);
+                    best[next] = best[next].min(candidate);
+                }
+            }
+        }
+        best[sink]
+    }
+
+    fn path_cost(path: &[Bathymetry], cost: &dyn CostFunction) -> f64 {
+        path.windows(2)
+            .map(|w| cost.cost(&w[0], &w[1], w[0].distance_to(&w[1])))
+            .sum()
+    }
+
+    #[test]
+    fn thalweg_finds_minimum_cost_path() {
+        let costs = [
+            Cost::Uniform,
+            Cost::Distance,
+            Cost::DepthRelative(100.0),
+            Cost::InverseDepth(1.0),
+            Cost::ExponentialDepth(50.0),
+            Cost::Slope(10.0),
+        ];
+        for seed in 0..3 {
+            let data = synthetic_grid(8, seed);
+            let max_depth = data.iter().map(Bathymetry::depth).fold(0.0, f64::max);
+            for &(source, sink) in &[(0, 63), (7, 56), (3, 60)] {
+                for cost in costs {
+                    let generator = ThalwegGenerator::new(data.clone(), 80, false).with_cost_function(cost.build(max_depth));
+                    let path = generator.thalweg(data[source].point(), data[sink].point()).unwrap();
+                    assert_eq!(path.first(), Some(&data[source]));
+                    assert_eq!(path.last(), Some(&data[sink]));
+                    assert!(path.windows(2).all(|w| w[0].distance_to(&w[1]) <= 80.0));
+
+                    let function = cost.build(max_depth);
+                    let expected = minimum_cost(&data, 80.0, function.as_ref(), source, sink);
+                    let found = path_cost(&path, function.as_ref());
+                    assert!((found - expected).abs() < 1e-6, "{}: found {} expected {}", cost, found, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn thalweg_cost_does_not_depend_on_resolution_scale() {
+        // a straight line of points is the cheapest distance path whether taken in one hop or several
+        let data: Vec<Bathymetry> = (0..5).map(|i| Bathymetry::new(0.0, i as f64 * 0.0005, 10.0)).collect();
+        let cost = Cost::Distance.build(10.0);
+        let short_hops = ThalwegGenerator::new(data.clone(), 60, false).with_cost_function(Cost::Distance.build(10.0));
+        let long_hops = ThalwegGenerator::new(data.clone(), 250, false).with_cost_function(Cost::Distance.build(10.0));
+        let short_path = short_hops.thalweg(data[0].point(), data[4].point()).unwrap();
+        let long_path = long_hops.thalweg(data[0].point(), data[4].point()).unwrap();
+        assert_eq!(short_path.len(), 5);
+        assert!((path_cost(&short_path, cost.as_ref()) - path_cost(&long_path, cost.as_ref())).abs() < 1e-6);
+    }
+
+    #[test]
+    fn thalweg_is_the_same_with_either_index() {
+        // leave some cells empty, as land would in a raster
+        let data: Vec<Bathymetry> = synthetic_grid(8, 1)
+            .into_iter()
+            .enumerate()
+            .filter(|(id, _)| id % 5 != 3)
+            .map(|(_, node)| node)
+            .collect();
+        let (source, sink) = (data[0].point(), data[data.len() - 1].point());
+        let mut paths = vec![];
+        for backend in [index::Backend::Grid, index::Backend::Tree] {
+            let points = index::build(data.clone(), backend).unwrap();
+            let generator = ThalwegGenerator::from_index(points, 80, true);
+            paths.push(generator.thalweg(source, sink).unwrap());
+        }
+        assert_eq!(paths[0], paths[1]);
+    }
 }
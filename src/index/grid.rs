@@ -0,0 +1,399 @@
+use crate::bathymetry::{Bathymetry, Point};
+use crate::index::{Layout, PointIndex, EARTH_RADIUS};
+
+use std::ops::Range;
+
+// allow for the difference between haversine distances and the flat approximation used to bound searches
+const TOLERANCE: f64 = 1.01;
+
+// locations are stored in millionths of a degree
+const PRECISION: f64 = 1000000.0;
+
+// coordinates given in degrees, minutes and seconds, as NONNA's are, are rounded to a hundredth of a second
+const DMS_QUANTUM: f64 = PRECISION * 0.01 / 3600.0;
+
+// how far, in millionths of a degree, a point may be from its grid line after rounding: half the DMS quantum and
+// the truncation to millionths, for both the point and the first grid line it is measured from
+const SNAP: f64 = DMS_QUANTUM + 2.0;
+
+// grids with more cells than this per point are mostly empty, and better served by a tree
+const MAX_CELLS_PER_POINT: usize = 16;
+
+const NODATA: usize = usize::MAX;
+
+/// Evenly spaced grid lines along one axis, in millionths of a degree
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Axis {
+    pub(crate) origin: f64,
+    pub(crate) step: f64,
+    pub(crate) count: usize,
+}
+
+impl Axis {
+    /// Grid lines through every value, if they are evenly spaced
+    fn detect(values: impl Iterator<Item = isize>) -> Option<Self> {
+        let mut values: Vec<isize> = values.collect();
+        values.sort_unstable();
+        values.dedup();
+        let first = *values.first()?;
+        let last = *values.last()?;
+        let min_gap = values.windows(2).map(|pair| pair[1] - pair[0]).min()?;
+        if min_gap as f64 <= 2.0 * SNAP {
+            return None;
+        }
+        // the smallest gap may be off by the rounding of the coordinates, so the step is refined along the values,
+        // each being further from the first and its error shared over more intervals
+        let mut step = min_gap as f64;
+        for &value in &values {
+            let offset = (value - first) as f64;
+            let index = (offset / step).round();
+            if index >= 1.0 {
+                step = offset / index;
+            }
+        }
+        let intervals = ((last - first) as f64 / step).round();
+        let axis = Self {
+            origin: first as f64,
+            step: (last - first) as f64 / intervals,
+            count: intervals as usize + 1,
+        };
+        values.iter().all(|&value| axis.index(value).is_some()).then_some(axis)
+    }
+
+    /// Grid line that `value` lies on
+    fn index(&self, value: isize) -> Option<usize> {
+        let index = ((value as f64 - self.origin) / self.step).round();
+        let on_line = (self.origin + index * self.step - value as f64).abs() <= SNAP;
+        (on_line && index >= 0.0 && (index as usize) < self.count).then_some(index as usize)
+    }
+
+    /// Fractional grid line of a coordinate in degrees
+    fn position(&self, degrees: f64) -> f64 {
+        (degrees * PRECISION - self.origin) / self.step
+    }
+
+    /// Grid line closest to a coordinate in degrees
+    fn nearest(&self, degrees: f64) -> usize {
+        self.position(degrees).round().clamp(0.0, (self.count - 1) as f64) as usize
+    }
+
+    /// Grid lines between two coordinates in degrees
+    fn between(&self, low: f64, high: f64) -> Range<usize> {
+        let margin = SNAP / self.step;
+        let start = (self.position(low) - margin).ceil().clamp(0.0, self.count as f64) as usize;
+        let end = (self.position(high) + margin).floor() + 1.0;
+        let end = end.clamp(0.0, self.count as f64) as usize;
+        start.min(end)..end
+    }
+
+    /// Coordinate of the grid line furthest from zero, in degrees
+    fn max_abs(&self) -> f64 {
+        let last = self.origin + self.step * (self.count - 1) as f64;
+        f64::max(self.origin.abs(), last.abs()) / PRECISION
+    }
+
+    fn metres(&self, scale: f64) -> f64 {
+        (self.step / PRECISION).to_radians() * EARTH_RADIUS * scale
+    }
+}
+
+/// Index over points on a regular latitude-longitude grid, such as rasters
+///
+/// Points are kept in a dense array of cells, row by row from the south-west corner, with cells without data
+/// marked as such. Neighbourhood and nearest-point queries then only visit the cells around the query.
+pub struct GridIndex {
+    nodes: Vec<Bathymetry>,
+    /// Id of the point in each cell, or `NODATA`
+    cells: Vec<usize>,
+    columns: Axis,
+    rows: Axis,
+}
+
+impl GridIndex {
+    /// Index `nodes` as a grid, handing them back if they are not evenly spaced or the grid would be mostly empty
+    pub fn new(nodes: Vec<Bathymetry>) -> Result<Self, Vec<Bathymetry>> {
+        let columns = Axis::detect(nodes.iter().map(|node| node.location().0));
+        let rows = Axis::detect(nodes.iter().map(|node| node.location().1));
+        let (columns, rows) = match (columns, rows) {
+            (Some(columns), Some(rows)) => (columns, rows),
+            _ => return Err(nodes),
+        };
+        let size = match columns.count.checked_mul(rows.count) {
+            Some(size) if size <= nodes.len().saturating_mul(MAX_CELLS_PER_POINT) => size,
+            _ => return Err(nodes),
+        };
+
+        let mut cells = vec![NODATA; size];
+        let mut duplicate = false;
+        for (id, node) in nodes.iter().enumerate() {
+            let (longitude, latitude) = node.location();
+            // every coordinate was checked to be on a grid line while detecting the axes
+            let column = columns.index(longitude).expect("longitude off the grid");
+            let row = rows.index(latitude).expect("latitude off the grid");
+            let cell = &mut cells[row * columns.count + column];
+            if *cell != NODATA {
+                duplicate = true;
+                break;
+            }
+            *cell = id;
+        }
+        if duplicate {
+            return Err(nodes);
+        }
+
+        Ok(Self {
+            nodes,
+            cells,
+            columns,
+            rows,
+        })
+    }
+
+    /// Rebuild a grid from its parts as saved, checking that they fit together
+    pub(crate) fn from_parts(nodes: Vec<Bathymetry>, columns: Axis, rows: Axis, cells: Vec<usize>) -> Option<Self> {
+        let fits = columns.count >= 1
+            && rows.count >= 1
+            && columns.step > 0.0
+            && rows.step > 0.0
+            && columns.count.checked_mul(rows.count) == Some(cells.len())
+            && cells.iter().all(|&id| id == NODATA || id < nodes.len());
+        fits.then_some(Self {
+            nodes,
+            cells,
+            columns,
+            rows,
+        })
+    }
+
+    pub(crate) fn columns(&self) -> Axis {
+        self.columns
+    }
+
+    pub(crate) fn rows(&self) -> Axis {
+        self.rows
+    }
+
+    /// Id of the point in each cell, row by row, with `usize::MAX` where there is no data
+    pub(crate) fn cells(&self) -> &[usize] {
+        &self.cells
+    }
+
+    /// Depth at `point` interpolated bilinearly between the grid points around it, weighting only those with data
+    /// for which `predicate` holds
+    pub fn bilinear_where(&self, &(longitude, latitude): &Point, predicate: &dyn Fn(&Bathymetry) -> bool) -> Option<f64> {
+        let x = self.columns.position(longitude);
+        let y = self.rows.position(latitude);
+        let (last_column, last_row) = ((self.columns.count - 1) as f64, (self.rows.count - 1) as f64);
+        if !(0.0..=last_column).contains(&x) || !(0.0..=last_row).contains(&y) {
+            return None;
+        }
+        // the cell to the south west, stepping back from the last line so its neighbours are on the grid
+        let column = x.floor().min((last_column - 1.0).max(0.0));
+        let row = y.floor().min((last_row - 1.0).max(0.0));
+        let (fx, fy) = (x - column, y - row);
+        let corners = [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ];
+        let (mut total, mut weights) = (0.0, 0.0);
+        for (dc, dr, weight) in corners {
+            let (c, r) = (column as usize + dc, row as usize + dr);
+            if weight == 0.0 || c >= self.columns.count || r >= self.rows.count {
+                continue;
+            }
+            let id = self.cells[r * self.columns.count + c];
+            if id != NODATA && predicate(&self.nodes[id]) {
+                total += weight * self.nodes[id].depth();
+                weights += weight;
+            }
+        }
+        (weights > 0.0).then(|| total / weights)
+    }
+
+    /// Cells whose column or row is exactly `ring` away from the given cell
+    fn ring(&self, column: usize, row: usize, ring: usize) -> impl Iterator<Item = usize> + '_ {
+        let (column, row, ring) = (column as isize, row as isize, ring as isize);
+        let width = self.columns.count as isize;
+        let height = self.rows.count as isize;
+        (row - ring..=row + ring)
+            .filter(move |&r| r >= 0 && r < height)
+            .flat_map(move |r| {
+                // whole rows along the top and bottom, only the ends of the rows in between
+                let edge = r == row - ring || r == row + ring;
+                let step = if edge { 1 } else { 2 * ring as usize };
+                (column - ring..=column + ring)
+                    .step_by(step)
+                    .filter(move |&c| c >= 0 && c < width)
+                    .map(move |c| (r * width + c) as usize)
+            })
+    }
+}
+
+impl PointIndex for GridIndex {
+    fn nodes(&self) -> &[Bathymetry] {
+        &self.nodes
+    }
+
+    fn nearest_where(&self, point: &Point, predicate: &dyn Fn(&Bathymetry) -> bool) -> Option<usize> {
+        let (longitude, latitude) = *point;
+        let column = self.columns.nearest(longitude);
+        let row = self.rows.nearest(latitude);
+        // columns are closest together at the latitude furthest from the equator
+        let scale = f64::max(self.rows.max_abs(), latitude.abs()).min(90.0).to_radians().cos();
+        let cell_metres = f64::min(self.columns.metres(scale), self.rows.metres(1.0));
+
+        let mut best: Option<(usize, f64)> = None;
+        for ring in 0..usize::max(self.columns.count, self.rows.count) {
+            // every cell on this ring or beyond is at least this far away
+            let bound = (ring as f64 - 0.5).max(0.0) * cell_metres / TOLERANCE;
+            if best.is_some_and(|(_, distance)| distance < bound) {
+                break;
+            }
+            for id in self.ring(column, row, ring).map(|cell| self.cells[cell]) {
+                if id == NODATA {
+                    continue;
+                }
+                let candidate = &self.nodes[id];
+                let distance = candidate.distance_to_point(point);
+                match best {
+                    Some((_, best_distance)) if best_distance <= distance => {}
+                    _ if predicate(candidate) => best = Some((id, distance)),
+                    _ => {}
+                }
+            }
+        }
+        best.map(|(id, _)| id)
+    }
+
+    fn within(&self, point: &Point, radius: f64) -> Box<dyn Iterator<Item = usize> + '_> {
+        let (longitude, latitude) = *point;
+        let latitude_reach = (radius * TOLERANCE / EARTH_RADIUS).to_degrees();
+        let rows = self.rows.between(latitude - latitude_reach, latitude + latitude_reach);
+        // a degree of longitude is shortest at the edge of the window furthest from the equator
+        let scale = (latitude.abs() + latitude_reach).min(90.0).to_radians().cos();
+        let longitude_reach = latitude_reach / scale;
+        let columns = if longitude_reach.is_finite() && longitude_reach < 180.0 {
+            self.columns.between(longitude - longitude_reach, longitude + longitude_reach)
+        } else {
+            0..self.columns.count
+        };
+
+        let width = self.columns.count;
+        let point = *point;
+        Box::new(
+            rows.flat_map(move |row| columns.clone().map(move |column| row * width + column))
+                .map(move |cell| self.cells[cell])
+                .filter(|&id| id != NODATA)
+                .filter(move |&id| self.nodes[id].distance_to_point(&point) <= radius),
+        )
+    }
+
+    fn layout(&self) -> Layout<'_> {
+        Layout::Grid(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::index::tests::{check_nearest, check_within};
+
+    // points at high latitude, where a degree of longitude is much shorter than a degree of latitude
+    fn northern_grid() -> Vec<Bathymetry> {
+        let mut out = vec![];
+        for i in 0..20 {
+            for j in 0..20 {
+                out.push(Bathymetry::new(60.0 + i as f64 * 0.0007, -135.0 + j as f64 * 0.0011, 10.0));
+            }
+        }
+        out
+    }
+
+    // the northern grid with a scattering of cells missing, as a raster with nodata would have
+    fn grid_with_holes() -> Vec<Bathymetry> {
+        northern_grid()
+            .into_iter()
+            .enumerate()
+            .filter(|(id, _)| id % 3 != 1 && id % 7 != 2)
+            .map(|(_, node)| node)
+            .collect()
+    }
+
+    #[test]
+    fn detects_grid_spacing() {
+        let index = GridIndex::new(northern_grid()).ok().unwrap();
+        assert_eq!((index.columns.count, index.rows.count), (20, 20));
+        assert!((index.columns.step - 1100.0).abs() < SNAP);
+        assert!((index.rows.step - 700.0).abs() < SNAP);
+    }
+
+    #[test]
+    fn detects_grids_rounded_to_hundredths_of_a_second() {
+        // a NONNA grid 0.324" apart, read from coordinates given to 0.01"
+        let dms = |origin: f64, step: usize| origin + (step as f64 * 32.4).round() / 360000.0;
+        let nodes: Vec<Bathymetry> = (0..300)
+            .flat_map(|i| (0..40).map(move |j| Bathymetry::new(dms(49.0, i), dms(-123.5, j), 10.0)))
+            .collect();
+        let index = GridIndex::new(nodes.clone()).ok().unwrap();
+        assert_eq!((index.columns.count, index.rows.count), (40, 300));
+        check_nearest(&index, &nodes);
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let nodes = northern_grid();
+        check_nearest(&GridIndex::new(nodes.clone()).ok().unwrap(), &nodes);
+        let nodes = grid_with_holes();
+        check_nearest(&GridIndex::new(nodes.clone()).ok().unwrap(), &nodes);
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let nodes = northern_grid();
+        check_within(&GridIndex::new(nodes.clone()).ok().unwrap(), &nodes);
+        let nodes = grid_with_holes();
+        check_within(&GridIndex::new(nodes.clone()).ok().unwrap(), &nodes);
+    }
+
+    #[test]
+    fn nearest_where_skips_rejected_points() {
+        let nodes = northern_grid();
+        let index = GridIndex::new(nodes.clone()).ok().unwrap();
+        let point = (-135.0, 60.0);
+        let id = index.nearest_where(&point, &|node| node.point().0 > -134.995).unwrap();
+        assert!(nodes[id].point().0 > -134.995);
+        assert_eq!(nodes[id].point().1, 60.0);
+    }
+
+    #[test]
+    fn rejects_duplicate_and_sparse_points() {
+        let mut nodes = northern_grid();
+        nodes.push(nodes[0].clone());
+        assert!(GridIndex::new(nodes).is_err());
+        // two corners of a large grid with nothing in between
+        let corners = vec![Bathymetry::new(0.0, 0.0, 1.0), Bathymetry::new(1.0, 1.0, 1.0), Bathymetry::new(0.001, 0.0, 1.0)];
+        assert!(GridIndex::new(corners).is_err());
+    }
+
+    #[test]
+    fn bilinear_between_grid_points() {
+        let nodes = vec![
+            Bathymetry::new(0.0, 0.0, 10.0),
+            Bathymetry::new(0.0, 0.001, 20.0),
+            Bathymetry::new(0.001, 0.0, 30.0),
+            Bathymetry::new(0.001, 0.001, 40.0),
+            Bathymetry::new(0.0, 0.002, 50.0),
+        ];
+        let index = GridIndex::new(nodes).ok().unwrap();
+        assert_eq!(index.bilinear_where(&(0.0, 0.0), &|_| true), Some(10.0));
+        assert!((index.bilinear_where(&(0.0005, 0.0005), &|_| true).unwrap() - 25.0).abs() < 1e-9);
+        assert!((index.bilinear_where(&(0.00025, 0.001), &|_| true).unwrap() - 32.5).abs() < 1e-9);
+        // only the points with data count
+        assert!((index.bilinear_where(&(0.0015, 0.0005), &|_| true).unwrap() - 110.0 / 3.0).abs() < 1e-9);
+        assert_eq!(index.bilinear_where(&(0.003, 0.0), &|_| true), None);
+        assert_eq!(index.bilinear_where(&(0.0005, 0.0), &|node| node.depth() > 10.0), Some(20.0));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::bathymetry::{Bathymetry, Point};
+use crate::error::Error;
+
+use std::fmt;
+use std::str;
+
+mod grid;
+mod tree;
+
+pub(crate) use grid::Axis;
+pub use grid::GridIndex;
+pub use tree::TreeIndex;
+
+/// Mean radius of the earth in metres, as used by the haversine distance
+pub(crate) const EARTH_RADIUS: f64 = 6371008.8;
+
+/// Bathymetry stored in a flat array, addressed by id, with spatial queries over the ids
+pub trait PointIndex {
+    /// Every point, indexed by id
+    fn nodes(&self) -> &[Bathymetry];
+
+    /// Closest point to `point` for which `predicate` holds
+    fn nearest_where(&self, point: &Point, predicate: &dyn Fn(&Bathymetry) -> bool) -> Option<usize>;
+
+    /// Ids of the points within `radius` metres of `point`, in no particular order
+    fn within(&self, point: &Point, radius: f64) -> Box<dyn Iterator<Item = usize> + '_>;
+
+    /// How the points are arranged, so the index can be saved and rebuilt without detecting it again
+    fn layout(&self) -> Layout<'_>;
+
+    fn len(&self) -> usize {
+        self.nodes().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nodes().is_empty()
+    }
+
+    fn get(&self, id: usize) -> &Bathymetry {
+        &self.nodes()[id]
+    }
+
+    fn nearest(&self, point: &Point) -> Option<usize> {
+        self.nearest_where(point, &|_| true)
+    }
+}
+
+/// Arrangement of the points held by an index
+pub enum Layout<'a> {
+    Scattered,
+    Grid(&'a GridIndex),
+}
+
+/// How points are stored for searching
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Use a grid when the points lie on one, otherwise a tree
+    #[default]
+    Auto,
+    /// Dense grid, for regularly spaced data such as rasters
+    Grid,
+    /// R-tree, for scattered points such as soundings
+    Tree,
+}
+
+impl str::FromStr for Backend {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Backend::Auto),
+            "grid" => Ok(Backend::Grid),
+            "tree" => Ok(Backend::Tree),
+            _ => Err("unrecognized index"),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Auto => write!(f, "auto"),
+            Backend::Grid => write!(f, "grid"),
+            Backend::Tree => write!(f, "tree"),
+        }
+    }
+}
+
+/// Index `nodes` with the chosen backend, failing if a grid is asked for but the points do not lie on one
+pub fn build(nodes: Vec<Bathymetry>, backend: Backend) -> Result<Box<dyn PointIndex>, Error> {
+    match backend {
+        Backend::Auto => Ok(auto(nodes)),
+        Backend::Grid => match GridIndex::new(nodes) {
+            Ok(grid) => Ok(Box::new(grid)),
+            Err(_) => Err(Error::InvalidInput("points do not lie on a regular grid".to_string())),
+        },
+        Backend::Tree => Ok(Box::new(TreeIndex::new(nodes))),
+    }
+}
+
+/// Index `nodes` as a grid when they lie on one, otherwise with a tree
+pub fn auto(nodes: Vec<Bathymetry>) -> Box<dyn PointIndex> {
+    match GridIndex::new(nodes) {
+        Ok(grid) => Box::new(grid),
+        Err(nodes) => Box::new(TreeIndex::new(nodes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // brute force search to compare against
+    fn closest(nodes: &[Bathymetry], point: &Point) -> usize {
+        (0..nodes.len())
+            .min_by(|&a, &b| {
+                nodes[a]
+                    .distance_to_point(point)
+                    .total_cmp(&nodes[b].distance_to_point(point))
+            })
+            .unwrap()
+    }
+
+    pub(super) fn check_nearest(index: &dyn PointIndex, nodes: &[Bathymetry]) {
+        for &point in &[(-135.0, 60.0), (-134.99, 60.005), (-134.985, 60.0131), (-134.9, 59.9)] {
+            let id = index.nearest(&point).unwrap();
+            assert_eq!(nodes[id].distance_to_point(&point), nodes[closest(nodes, &point)].distance_to_point(&point));
+        }
+    }
+
+    pub(super) fn check_within(index: &dyn PointIndex, nodes: &[Bathymetry]) {
+        let point = (-134.99, 60.006);
+        for &radius in &[50.0, 100.0, 250.0] {
+            let mut found: Vec<usize> = index.within(&point, radius).collect();
+            found.sort_unstable();
+            let expected: Vec<usize> = (0..nodes.len())
+                .filter(|&id| nodes[id].distance_to_point(&point) <= radius)
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn auto_falls_back_to_tree_for_scattered_points() {
+        let nodes = vec![
+            Bathymetry::new(0.0, 0.0, 1.0),
+            Bathymetry::new(0.0013, 0.0007, 2.0),
+            Bathymetry::new(0.0021, 0.0031, 3.0),
+        ];
+        assert!(GridIndex::new(nodes.clone()).is_err());
+        assert!(build(nodes.clone(), Backend::Grid).is_err());
+        let index = auto(nodes);
+        assert_eq!(index.nearest(&(0.003, 0.002)), Some(2));
+    }
+
+    #[test]
+    fn parse_backend_names() {
+        assert_eq!("Grid".parse(), Ok(Backend::Grid));
+        assert_eq!(Backend::Tree.to_string().parse(), Ok(Backend::Tree));
+        assert!("quadtree".parse::<Backend>().is_err());
+    }
+}
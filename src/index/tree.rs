@@ -0,0 +1,151 @@
+use crate::bathymetry::{Bathymetry, Point};
+use crate::index::{Layout, PointIndex, EARTH_RADIUS};
+
+use rstar::primitives::GeomWithData;
+use rstar::RTree;
+
+// allow for rounding in the chord and haversine distances when searching the tree
+const TOLERANCE: f64 = 1.0001;
+
+type Node = GeomWithData<[f64; 3], usize>;
+
+/// Index over scattered points
+///
+/// The R-tree holds each point at its position in three dimensions on a sphere of the Earth's radius. The
+/// straight chord between two points is never longer than the great circle between them, and grows with it,
+/// so the nearest points along the chord are the nearest by haversine distance wherever they and the query lie.
+/// Searches are done on the chords and confirmed with the haversine distance.
+pub struct TreeIndex {
+    nodes: Vec<Bathymetry>,
+    tree: RTree<Node>,
+}
+
+impl TreeIndex {
+    pub fn new(nodes: Vec<Bathymetry>) -> Self {
+        let tree = RTree::bulk_load(
+            nodes
+                .iter()
+                .enumerate()
+                .map(|(id, node)| Node::new(project(node.point()), id))
+                .collect(),
+        );
+        Self { nodes, tree }
+    }
+}
+
+impl PointIndex for TreeIndex {
+    fn nodes(&self) -> &[Bathymetry] {
+        &self.nodes
+    }
+
+    fn nearest_where(&self, point: &Point, predicate: &dyn Fn(&Bathymetry) -> bool) -> Option<usize> {
+        let query = project(*point);
+        let mut best: Option<(usize, f64)> = None;
+        for node in self.tree.nearest_neighbor_iter(&query) {
+            // points come in order of their chords, so nothing further along can be closer
+            let chord = distance(node.geom(), &query);
+            if best.is_some_and(|(_, distance)| chord > chord_of(distance) * TOLERANCE) {
+                break;
+            }
+            let candidate = &self.nodes[node.data];
+            let distance = candidate.distance_to_point(point);
+            match best {
+                Some((_, best_distance)) if best_distance <= distance => {}
+                _ if predicate(candidate) => best = Some((node.data, distance)),
+                _ => {}
+            }
+        }
+        best.map(|(id, _)| id)
+    }
+
+    fn within(&self, point: &Point, radius: f64) -> Box<dyn Iterator<Item = usize> + '_> {
+        let query = project(*point);
+        let chord_radius = chord_of(radius) * TOLERANCE;
+        let point = *point;
+        Box::new(
+            self.tree
+                .locate_within_distance(query, chord_radius * chord_radius)
+                .map(|node| node.data)
+                .filter(move |&id| self.nodes[id].distance_to_point(&point) <= radius),
+        )
+    }
+
+    fn layout(&self) -> Layout<'_> {
+        Layout::Scattered
+    }
+}
+
+fn project((longitude, latitude): Point) -> [f64; 3] {
+    let (longitude, latitude) = (longitude.to_radians(), latitude.to_radians());
+    [
+        EARTH_RADIUS * latitude.cos() * longitude.cos(),
+        EARTH_RADIUS * latitude.cos() * longitude.sin(),
+        EARTH_RADIUS * latitude.sin(),
+    ]
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Length of the chord under a great circle of `distance` metres
+fn chord_of(distance: f64) -> f64 {
+    let angle = (distance / EARTH_RADIUS).min(std::f64::consts::PI);
+    2.0 * EARTH_RADIUS * (angle / 2.0).sin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::index::tests::{check_nearest, check_within};
+
+    // points at high latitude, where a degree of longitude is much shorter than a degree of latitude
+    fn northern_grid() -> Vec<Bathymetry> {
+        let mut out = vec![];
+        for i in 0..20 {
+            for j in 0..20 {
+                out.push(Bathymetry::new(60.0 + i as f64 * 0.0007, -135.0 + j as f64 * 0.0011, 10.0));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let nodes = northern_grid();
+        check_nearest(&TreeIndex::new(nodes.clone()), &nodes);
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let nodes = northern_grid();
+        check_within(&TreeIndex::new(nodes.clone()), &nodes);
+    }
+
+    #[test]
+    fn nearest_where_skips_rejected_points() {
+        let nodes = northern_grid();
+        let index = TreeIndex::new(nodes.clone());
+        let point = (-135.0, 60.0);
+        let id = index.nearest_where(&point, &|node| node.point().0 > -134.995).unwrap();
+        assert!(nodes[id].point().0 > -134.995);
+        assert_eq!(nodes[id].point().1, 60.0);
+    }
+
+    #[test]
+    fn nearest_holds_poleward_of_the_data() {
+        // 31 degrees from the query along its meridian, and just under 30 degrees a quarter of the way round
+        let nodes = vec![Bathymetry::new(58.0, 0.0, 10.0), Bathymetry::new(60.0, 90.0, 10.0)];
+        let index = TreeIndex::new(nodes.clone());
+        assert_eq!(index.nearest(&(0.0, 89.0)), Some(1));
+        check_nearest(&index, &nodes);
+    }
+
+    #[test]
+    fn empty_index_has_no_nearest() {
+        let index = TreeIndex::new(vec![]);
+        assert!(index.is_empty());
+        assert_eq!(index.nearest(&(0.0, 0.0)), None);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::bathymetry::{Bathymetry, Point};
+use crate::index::{Layout, PointIndex, EARTH_RADIUS};
+
+use std::fmt;
+use std::str;
+
+// positions closer than this many metres to a sounding take its depth
+const COINCIDENT: f64 = 0.001;
+
+// inverse-distance weighting waits for this many soundings before settling on a neighbourhood
+const MIN_NEIGHBOURS: usize = 4;
+
+// triangulations are built from at most this many of the nearest soundings
+const MAX_NEIGHBOURS: usize = 32;
+
+/// How depths are found at positions between soundings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Take the nearest sounding, moving the position onto it
+    #[default]
+    Nearest,
+    /// Bilinear between the four grid points around the position. Only available for gridded bathymetry
+    Bilinear,
+    /// Inverse-distance weighting of the soundings around the position
+    InverseDistance,
+    /// Linear across the triangle containing the position in the Delaunay triangulation of the soundings (TIN)
+    Triangulated,
+}
+
+impl str::FromStr for Interpolation {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nearest" => Ok(Interpolation::Nearest),
+            "bilinear" => Ok(Interpolation::Bilinear),
+            "idw" => Ok(Interpolation::InverseDistance),
+            "tin" => Ok(Interpolation::Triangulated),
+            _ => Err("unrecognized interpolation"),
+        }
+    }
+}
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interpolation::Nearest => write!(f, "nearest"),
+            Interpolation::Bilinear => write!(f, "bilinear"),
+            Interpolation::InverseDistance => write!(f, "idw"),
+            Interpolation::Triangulated => write!(f, "tin"),
+        }
+    }
+}
+
+/// Depth at exactly `point`, using only soundings for which `usable` holds
+///
+/// For inverse-distance weighting and triangulation, soundings are gathered from a circle twice as wide as the
+/// distance to the nearest one, doubling until there are enough to interpolate from, and none further than `limit`
+/// metres is used. The nearest sounding and the grid points around the position for bilinear interpolation are
+/// used however far away they are. There is no depth where the point lies outside the data: off the grid,
+/// outside the triangulation, or with no sounding in reach.
+pub fn depth_at(
+    points: &dyn PointIndex,
+    point: Point,
+    interpolation: Interpolation,
+    limit: f64,
+    usable: &dyn Fn(&Bathymetry) -> bool,
+) -> Option<f64> {
+    let nearest = points.get(points.nearest_where(&point, usable)?);
+    let distance = nearest.distance_to_point(&point);
+    match interpolation {
+        Interpolation::Nearest => Some(nearest.depth()),
+        Interpolation::Bilinear => match points.layout() {
+            Layout::Grid(grid) => grid.bilinear_where(&point, usable),
+            Layout::Scattered => None,
+        },
+        _ if distance < COINCIDENT => Some(nearest.depth()),
+        Interpolation::InverseDistance => neighbourhood(points, point, distance, limit, usable, |nodes, last| {
+            (last || nodes.len() >= MIN_NEIGHBOURS).then(|| inverse_distance(nodes, point))
+        }),
+        Interpolation::Triangulated => neighbourhood(points, point, distance, limit, usable, |nodes, _| {
+            let vertices: Vec<(f64, f64, f64)> = nodes
+                .iter()
+                .take(MAX_NEIGHBOURS)
+                .map(|node| {
+                    let (x, y) = project(point, node.point());
+                    (x, y, node.depth())
+                })
+                .collect();
+            triangulated(&vertices)
+        }),
+    }
+}
+
+/// First result of `attempt` on the usable soundings within a widening circle, nearest first, and whether the
+/// circle has reached `limit`
+fn neighbourhood(
+    points: &dyn PointIndex,
+    point: Point,
+    nearest: f64,
+    limit: f64,
+    usable: &dyn Fn(&Bathymetry) -> bool,
+    attempt: impl Fn(&[&Bathymetry], bool) -> Option<f64>,
+) -> Option<f64> {
+    let mut radius = f64::min(2.0 * nearest, limit);
+    while radius >= nearest {
+        let mut nodes: Vec<&Bathymetry> = points
+            .within(&point, radius)
+            .map(|id| points.get(id))
+            .filter(|node| usable(node))
+            .collect();
+        nodes.sort_by(|a, b| a.distance_to_point(&point).total_cmp(&b.distance_to_point(&point)));
+        let last = radius >= limit;
+        if let Some(depth) = attempt(&nodes, last) {
+            return Some(depth);
+        }
+        if last {
+            break;
+        }
+        radius = f64::min(2.0 * radius, limit);
+    }
+    None
+}
+
+/// Mean depth weighted by the inverse square of the distance from `point`
+fn inverse_distance(nodes: &[&Bathymetry], point: Point) -> f64 {
+    let (total, weights) = nodes.iter().fold((0.0, 0.0), |(total, weights), node| {
+        let weight = node.distance_to_point(&point).powi(-2);
+        (total + weight * node.depth(), weights + weight)
+    });
+    total / weights
+}
+
+/// Offset of `other` from `origin` in metres east and north, on a plane tangent at `origin`
+fn project((longitude, latitude): Point, (other_longitude, other_latitude): Point) -> (f64, f64) {
+    let x = (other_longitude - longitude).to_radians() * EARTH_RADIUS * latitude.to_radians().cos();
+    let y = (other_latitude - latitude).to_radians() * EARTH_RADIUS;
+    (x, y)
+}
+
+/// Value at the origin interpolated across the triangle containing it in the Delaunay triangulation of
+/// `vertices`, given as (x, y, value), found with the Bowyer-Watson algorithm
+fn triangulated(vertices: &[(f64, f64, f64)]) -> Option<f64> {
+    let count = vertices.len();
+    if count < 3 {
+        return None;
+    }
+    // a super triangle comfortably enclosing every vertex, removed again at the end
+    let extent = 100.0 * vertices.iter().map(|&(x, y, _)| x.abs().max(y.abs())).fold(1.0, f64::max);
+    let mut corners: Vec<(f64, f64)> = vertices.iter().map(|&(x, y, _)| (x, y)).collect();
+    corners.extend([(-extent, -extent), (extent, -extent), (0.0, extent)]);
+    let mut triangles = vec![[count, count + 1, count + 2]];
+
+    for vertex in 0..count {
+        let (bad, good): (Vec<[usize; 3]>, Vec<[usize; 3]>) = triangles
+            .into_iter()
+            .partition(|&[a, b, c]| in_circumcircle(corners[vertex], corners[a], corners[b], corners[c]));
+        // the edges around the hole left by the triangles removed are those only one of them has
+        let edges = bad.iter().flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)]);
+        let boundary: Vec<(usize, usize)> = edges
+            .filter(|&(a, b)| bad.iter().filter(|triangle| triangle.contains(&a) && triangle.contains(&b)).count() == 1)
+            .collect();
+        triangles = good;
+        triangles.extend(boundary.into_iter().map(|(a, b)| [a, b, vertex]));
+    }
+
+    triangles.iter().filter(|triangle| triangle.iter().all(|&corner| corner < count)).find_map(|&[a, b, c]| {
+        let ((xa, ya, za), (xb, yb, zb), (xc, yc, zc)) = (vertices[a], vertices[b], vertices[c]);
+        let determinant = (yb - yc) * (xa - xc) + (xc - xb) * (ya - yc);
+        if determinant == 0.0 {
+            return None;
+        }
+        // barycentric coordinates of the origin
+        let wa = ((yb - yc) * -xc + (xc - xb) * -yc) / determinant;
+        let wb = ((yc - ya) * -xc + (xa - xc) * -yc) / determinant;
+        let wc = 1.0 - wa - wb;
+        let tolerance = -1e-9;
+        (wa >= tolerance && wb >= tolerance && wc >= tolerance).then_some(wa * za + wb * zb + wc * zc)
+    })
+}
+
+/// Whether `point` lies strictly inside the circle through `a`, `b` and `c`
+fn in_circumcircle(point: (f64, f64), a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    let (ax, ay) = (a.0 - point.0, a.1 - point.1);
+    let (bx, by) = (b.0 - point.0, b.1 - point.1);
+    let (cx, cy) = (c.0 - point.0, c.1 - point.1);
+    let determinant = (ax * ax + ay * ay) * (bx * cy - cx * by) - (bx * bx + by * by) * (ax * cy - cx * ay)
+        + (cx * cx + cy * cy) * (ax * by - bx * ay);
+    let orientation = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    determinant * orientation > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{GridIndex, TreeIndex};
+
+    // depth rising evenly eastwards, so every interpolation between soundings should agree with it
+    fn slope(longitude: f64) -> f64 {
+        10.0 + 10000.0 * longitude
+    }
+
+    fn scattered() -> TreeIndex {
+        let mut points = vec![];
+        for x in 0..=10 {
+            for y in 0..=10 {
+                // jitter the soundings off any grid
+                let (lon, lat) = (x as f64 * 0.001 + (y % 3) as f64 * 0.0002, y as f64 * 0.001 + (x % 2) as f64 * 0.0003);
+                points.push(Bathymetry::new(lat, lon, slope(lon)));
+            }
+        }
+        TreeIndex::new(points)
+    }
+
+    #[test]
+    fn triangulation_reproduces_a_plane() {
+        let points = scattered();
+        for &point in &[(0.0043, 0.0051), (0.0071, 0.0022), (0.005, 0.005)] {
+            let depth = depth_at(&points, point, Interpolation::Triangulated, 1000.0, &|_| true).unwrap();
+            assert!((depth - slope(point.0)).abs() < 0.01, "{} at {:?}", depth, point);
+        }
+        // well outside the soundings there is nothing to interpolate between
+        assert_eq!(depth_at(&points, (0.05, 0.05), Interpolation::Triangulated, 1000.0, &|_| true), None);
+    }
+
+    #[test]
+    fn inverse_distance_stays_between_neighbours() {
+        let points = scattered();
+        let point = (0.0043, 0.0051);
+        let depth = depth_at(&points, point, Interpolation::InverseDistance, 1000.0, &|_| true).unwrap();
+        assert!((depth - slope(point.0)).abs() < 5.0);
+        // on a sounding the depth is exactly its own
+        let sounding = points.get(points.nearest(&point).unwrap());
+        let exact = depth_at(&points, sounding.point(), Interpolation::InverseDistance, 1000.0, &|_| true);
+        assert_eq!(exact, Some(sounding.depth()));
+    }
+
+    #[test]
+    fn bilinear_needs_a_grid() {
+        let mut nodes = vec![];
+        for x in 0..=4 {
+            for y in 0..=4 {
+                nodes.push(Bathymetry::new(y as f64 * 0.001, x as f64 * 0.001, slope(x as f64 * 0.001)));
+            }
+        }
+        let grid = GridIndex::new(nodes.clone()).ok().unwrap();
+        let depth = depth_at(&grid, (0.0025, 0.0013), Interpolation::Bilinear, 1000.0, &|_| true).unwrap();
+        assert!((depth - 35.0).abs() < 1e-6);
+        let tree = TreeIndex::new(nodes);
+        assert_eq!(depth_at(&tree, (0.0025, 0.0013), Interpolation::Bilinear, 1000.0, &|_| true), None);
+    }
+
+    #[test]
+    fn parse_interpolation_names() {
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::InverseDistance,
+            Interpolation::Triangulated,
+        ] {
+            assert_eq!(interpolation.to_string().parse(), Ok(interpolation));
+        }
+        assert_eq!("IDW".parse(), Ok(Interpolation::InverseDistance));
+        assert!("spline".parse::<Interpolation>().is_err());
+    }
+}
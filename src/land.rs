@@ -0,0 +1,111 @@
+use crate::bathymetry::Point;
+
+use geo::algorithm::bounding_rect::BoundingRect;
+use geo::algorithm::intersects::Intersects;
+use geo::{Line, MultiPolygon, Polygon};
+
+use rstar::primitives::{GeomWithData, Line as Edge, Rectangle};
+use rstar::{RTree, AABB};
+
+type Envelope = GeomWithData<Rectangle<[f64; 2]>, usize>;
+
+/// Land that a thalweg may not cross
+///
+/// The bounding box of every polygon and every edge of their rings are held in R-trees, so that only the few
+/// polygons around a point and the few edges around a segment are tested exactly.
+pub struct LandMask {
+    polygons: Vec<Polygon<f64>>,
+    envelopes: RTree<Envelope>,
+    edges: RTree<Edge<[f64; 2]>>,
+}
+
+impl LandMask {
+    pub fn new(land: MultiPolygon<f64>) -> Self {
+        let polygons: Vec<Polygon<f64>> = land.into_iter().collect();
+        let envelopes = polygons
+            .iter()
+            .enumerate()
+            .filter_map(|(id, polygon)| {
+                let rect = polygon.bounding_rect()?;
+                let corners = Rectangle::from_corners([rect.min().x, rect.min().y], [rect.max().x, rect.max().y]);
+                Some(Envelope::new(corners, id))
+            })
+            .collect();
+        let edges = polygons
+            .iter()
+            .flat_map(|polygon| std::iter::once(polygon.exterior()).chain(polygon.interiors()))
+            .flat_map(|ring| ring.lines())
+            .map(|line| Edge::new([line.start.x, line.start.y], [line.end.x, line.end.y]))
+            .collect();
+        Self {
+            polygons,
+            envelopes: RTree::bulk_load(envelopes),
+            edges: RTree::bulk_load(edges),
+        }
+    }
+
+    /// Whether the point lies on land
+    pub fn contains(&self, point: Point) -> bool {
+        self.envelopes
+            .locate_all_at_point(&[point.0, point.1])
+            .any(|envelope| self.polygons[envelope.data].intersects(&geo::Point::from(point)))
+    }
+
+    /// Whether the straight segment between two points touches land
+    pub fn crosses(&self, a: Point, b: Point) -> bool {
+        // a segment touching no shoreline lies wholly on land or wholly off it, as its first point does
+        let line = Line::new(a, b);
+        let envelope = AABB::from_corners([a.0, a.1], [b.0, b.1]);
+        self.edges
+            .locate_in_envelope_intersecting(&envelope)
+            .any(|edge| Line::new((edge.from[0], edge.from[1]), (edge.to[0], edge.to[1])).intersects(&line))
+            || self.contains(a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::LineString;
+
+    // a square island
+    fn island() -> LandMask {
+        let square = Polygon::new(
+            LineString::from(vec![(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 2.0), (1.0, 1.0)]),
+            vec![],
+        );
+        LandMask::new(MultiPolygon(vec![square]))
+    }
+
+    #[test]
+    fn segment_across_island_crosses() {
+        assert!(island().crosses((0.0, 1.5), (3.0, 1.5)));
+    }
+
+    #[test]
+    fn segment_beside_island_does_not_cross() {
+        assert!(!island().crosses((0.0, 0.5), (3.0, 0.5)));
+        // overlapping bounding boxes alone are not enough
+        assert!(!island().crosses((0.0, 0.9), (1.5, 0.0)));
+    }
+
+    #[test]
+    fn segment_on_island_crosses() {
+        assert!(island().crosses((1.2, 1.5), (1.8, 1.5)));
+    }
+
+    #[test]
+    fn segment_within_lake_does_not_cross() {
+        let ring = |min: f64, max: f64| LineString::from(vec![(min, min), (max, min), (max, max), (min, max), (min, min)]);
+        let land = LandMask::new(MultiPolygon(vec![Polygon::new(ring(0.0, 3.0), vec![ring(1.0, 2.0)])]));
+        assert!(!land.crosses((1.2, 1.5), (1.8, 1.5)));
+        assert!(land.crosses((1.5, 1.5), (2.5, 1.5)));
+        assert!(!land.contains((1.5, 1.5)));
+    }
+
+    #[test]
+    fn contains_points_on_land() {
+        assert!(island().contains((1.5, 1.5)));
+        assert!(!island().contains((0.5, 1.5)));
+    }
+}
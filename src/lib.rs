@@ -1,6 +1,22 @@
+pub mod analysis;
 pub mod bathymetry;
+pub mod cache;
+pub mod centreline;
+pub mod cost;
+pub mod cross_section;
+pub mod error;
+pub mod flow;
 pub mod format;
 pub mod generator;
+pub mod index;
+pub mod interpolate;
+pub mod land;
+pub mod network;
 pub mod parse;
+pub mod plot;
 pub mod read;
 pub mod section;
+pub mod simplify;
+pub mod smooth;
+
+pub use error::Error;
@@ -0,0 +1,140 @@
+use crate::bathymetry::Bathymetry;
+use crate::error::Error;
+use crate::section::{self, Section};
+
+use json::{array, object, JsonValue};
+
+/// One arm of a thalweg network, running from where it leaves its parent to a head
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch {
+    pub id: usize,
+    /// Branch this one leaves, or none for the branch starting at the mouth
+    pub parent: Option<usize>,
+    /// Distance along the network from the mouth to the start of the branch in metres
+    pub start: isize,
+    /// Points from the junction with the parent, which is shared with it, to the head
+    pub path: Vec<Bathymetry>,
+}
+
+impl Branch {
+    /// Section along the branch, with distances measured from the mouth
+    pub fn section(&self) -> Vec<Section> {
+        section::section_from(&self.path, self.start)
+    }
+}
+
+/// Branches with `densify` applied to each path, such as `ThalwegGenerator::populate` or `resample`
+///
+/// Paths are densified piece by piece between the junctions where other branches leave them, so that every
+/// junction stays on its parent, and the start of each branch is measured again along its densified parent.
+/// Parents must come before the branches leaving them, as `ThalwegGenerator::network` gives them.
+pub fn densify<F>(branches: &[Branch], mut densify: F) -> Result<Vec<Branch>, Error>
+where
+    F: FnMut(&[Bathymetry]) -> Result<Vec<Bathymetry>, Error>,
+{
+    let mut out: Vec<Branch> = vec![];
+    for branch in branches {
+        let last = branch.path.len().saturating_sub(1);
+        let mut cuts: Vec<usize> = branches
+            .iter()
+            .filter(|other| other.parent == Some(branch.id))
+            .filter_map(|other| other.path.first())
+            .filter_map(|junction| branch.path.iter().position(|point| point == junction))
+            .chain([0, last])
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let mut path = branch.path.first().cloned().into_iter().collect::<Vec<_>>();
+        for pair in cuts.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let piece = densify(&branch.path[from..=to])?;
+            // the ends of each piece are the junctions themselves, whatever densifying made of them
+            let inner = piece.len().saturating_sub(1);
+            path.extend(piece.into_iter().take(inner).skip(1));
+            path.push(branch.path[to].clone());
+        }
+
+        let start = match branch.parent.and_then(|parent| out.iter().find(|other| other.id == parent)) {
+            Some(parent) => {
+                let junction = parent.path.iter().position(|point| Some(point) == path.first());
+                match junction {
+                    Some(index) => parent.section()[index].distance(),
+                    None => branch.start,
+                }
+            }
+            None => branch.start,
+        };
+        out.push(Branch {
+            id: branch.id,
+            parent: branch.parent,
+            start,
+            path,
+        });
+    }
+    Ok(out)
+}
+
+/// Branches as LineString features, identified by their `branch` and `parent` properties
+pub fn to_geojson(branches: &[Branch]) -> String {
+    let mut features = JsonValue::new_array();
+    for branch in branches {
+        let mut coordinates = JsonValue::new_array();
+        for point in &branch.path {
+            let (lon, lat) = point.point();
+            // pushing onto an array cannot fail
+            let _ = coordinates.push(array![lon, lat, -point.depth()]);
+        }
+        let _ = features.push(object! {
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": coordinates,
+            },
+            "properties": {
+                "branch": branch.id,
+                "parent": branch.parent,
+                "start": branch.start as f64 / 1000.0,
+            },
+        });
+    }
+    object! {
+        "type": "FeatureCollection",
+        "features": features,
+    }
+    .dump()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branches_as_features() {
+        let branches = vec![
+            Branch {
+                id: 0,
+                parent: None,
+                start: 0,
+                path: vec![Bathymetry::new(0.0, 0.0, 10.0), Bathymetry::new(0.0, 0.01, 20.0)],
+            },
+            Branch {
+                id: 1,
+                parent: Some(0),
+                start: 1112,
+                path: vec![Bathymetry::new(0.0, 0.01, 20.0), Bathymetry::new(0.01, 0.01, 5.0)],
+            },
+        ];
+        let expected = concat!(
+            "{\"type\":\"FeatureCollection\",\"features\":[",
+            "{\"type\":\"Feature\",\"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0,0,-10],[0.01,0,-20]]},",
+            "\"properties\":{\"branch\":0,\"parent\":null,\"start\":0}},",
+            "{\"type\":\"Feature\",\"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0.01,0,-20],[0.01,0.01,-5]]},",
+            "\"properties\":{\"branch\":1,\"parent\":0,\"start\":1.112}}",
+            "]}"
+        );
+        assert_eq!(to_geojson(&branches), expected);
+        assert_eq!(branches[1].section()[0].distance(), 1112);
+        assert_eq!(branches[1].section()[1].distance(), 1112 + 1111);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::error::Error;
+use crate::section::Section;
+
+use std::fmt::Write;
+
+/// Depth interval between ticks on the depth axis, in metres
+const THRESHOLD: f64 = 50.0;
+
+// space around the plot area for ticks and labels, in pixels
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 20.0;
+const MARGIN_BOTTOM: f64 = 55.0;
+
+// font for the labels of PNG plots, built in so that they render the same on machines with no fonts installed
+const FONT: &[u8] = include_bytes!("../fonts/DejaVuSansMono.ttf");
+const FONT_FAMILY: &str = "DejaVu Sans Mono";
+
+/// Size of a rendered section plot, in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlotOptions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+        }
+    }
+}
+
+/// Render a section as a filled depth profile in SVG
+pub fn to_svg(section: &[Section], options: &PlotOptions) -> String {
+    let width = options.width as f64;
+    let height = options.height as f64;
+    let plot_width = (width - MARGIN_LEFT - MARGIN_RIGHT).max(1.0);
+    let plot_height = (height - MARGIN_TOP - MARGIN_BOTTOM).max(1.0);
+
+    let points: Vec<(f64, f64)> = section
+        .iter()
+        .map(|s| (s.distance() as f64 / 1000.0, s.depth()))
+        .collect();
+    let start = points.first().map_or(0.0, |&(x, _)| x);
+    let end = points.last().map_or(0.0, |&(x, _)| x);
+    // avoid dividing by zero for a section of a single point
+    let span = if end > start { end - start } else { 1.0 };
+    let max_depth = points.iter().map(|&(_, y)| y).fold(0.0, f64::max);
+    let bottom = next_display_threshold(max_depth);
+
+    let to_x = |distance: f64| MARGIN_LEFT + (distance - start) / span * plot_width;
+    let to_y = |depth: f64| MARGIN_TOP + depth / bottom * plot_height;
+
+    let mut out = String::new();
+    // writing to a String cannot fail
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#,
+        w = options.width,
+        h = options.height
+    );
+    let _ = writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#);
+
+    if !points.is_empty() {
+        let profile: Vec<String> = points
+            .iter()
+            .map(|&(x, y)| format!("{:.2},{:.2}", to_x(x), to_y(y)))
+            .collect();
+        let _ = writeln!(
+            out,
+            r#"<polygon points="{:.2},{:.2} {} {:.2},{:.2}" fill="grey"/>"#,
+            to_x(start),
+            to_y(bottom),
+            profile.join(" "),
+            to_x(end),
+            to_y(bottom)
+        );
+        let _ = writeln!(
+            out,
+            r#"<polyline points="{}" fill="none" stroke="black" stroke-width="1.5"/>"#,
+            profile.join(" ")
+        );
+    }
+
+    // axes
+    let _ = writeln!(
+        out,
+        r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="none" stroke="black"/>"#,
+        MARGIN_LEFT, MARGIN_TOP, plot_width, plot_height
+    );
+
+    let steps = (bottom / THRESHOLD) as usize;
+    for step in 0..=steps {
+        let depth = step as f64 * THRESHOLD;
+        let y = to_y(depth);
+        let _ = writeln!(
+            out,
+            r#"<line x1="{:.2}" y1="{y:.2}" x2="{:.2}" y2="{y:.2}" stroke="black"/>"#,
+            MARGIN_LEFT - 5.0,
+            MARGIN_LEFT,
+            y = y
+        );
+        let _ = writeln!(
+            out,
+            r#"<text x="{:.2}" y="{:.2}" text-anchor="end">{}</text>"#,
+            MARGIN_LEFT - 8.0,
+            y + 4.0,
+            depth
+        );
+    }
+
+    let interval = tick_interval(span);
+    let first_tick = (start / interval).ceil() as i64;
+    let last_tick = (end / interval).floor() as i64;
+    // as many decimals as the interval has, so that labels are not written as 0.6000000000000001
+    let decimals = (-interval.log10().floor()).max(0.0) as usize;
+    for tick in first_tick..=last_tick {
+        let distance = tick as f64 * interval;
+        let x = to_x(distance);
+        let _ = writeln!(
+            out,
+            r#"<line x1="{x:.2}" y1="{:.2}" x2="{x:.2}" y2="{:.2}" stroke="black"/>"#,
+            MARGIN_TOP + plot_height,
+            MARGIN_TOP + plot_height + 5.0,
+            x = x
+        );
+        let _ = writeln!(
+            out,
+            r#"<text x="{:.2}" y="{:.2}" text-anchor="middle">{:.*}</text>"#,
+            x,
+            MARGIN_TOP + plot_height + 20.0,
+            decimals,
+            distance
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        r#"<text x="{:.2}" y="{:.2}" text-anchor="middle">Distance (km)</text>"#,
+        MARGIN_LEFT + plot_width / 2.0,
+        height - 10.0
+    );
+    let _ = writeln!(
+        out,
+        r#"<text x="{x:.2}" y="{y:.2}" text-anchor="middle" transform="rotate(-90 {x:.2} {y:.2})">Depth (m)</text>"#,
+        x = 18.0,
+        y = MARGIN_TOP + plot_height / 2.0
+    );
+    out += "</svg>\n";
+    out
+}
+
+/// Render a section as a filled depth profile in PNG, drawing the labels with the bundled font
+pub fn to_png(section: &[Section], options: &PlotOptions) -> Result<Vec<u8>, Error> {
+    let svg = to_svg(section, options);
+    let tree = usvg::Tree::from_str(&svg, &usvg_options().to_ref()).map_err(|e| Error::Render(e.to_string()))?;
+    let mut pixmap = tiny_skia::Pixmap::new(options.width, options.height)
+        .ok_or_else(|| Error::Render(format!("Invalid plot size {}x{}", options.width, options.height)))?;
+    resvg::render(&tree, usvg::FitTo::Original, tiny_skia::Transform::default(), pixmap.as_mut())
+        .ok_or_else(|| Error::Render("Unable to rasterise plot".to_string()))?;
+    pixmap.encode_png().map_err(|e| Error::Render(e.to_string()))
+}
+
+/// Options drawing every label with the bundled font, whatever fonts the system has
+fn usvg_options() -> usvg::Options {
+    let mut options = usvg::Options::default();
+    options.fontdb.load_font_data(FONT.to_vec());
+    options.fontdb.set_sans_serif_family(FONT_FAMILY);
+    options
+}
+
+/// Depth of the first tick beyond the deepest point
+fn next_display_threshold(depth: f64) -> f64 {
+    THRESHOLD * ((depth.trunc() / THRESHOLD).floor() + 1.0)
+}
+
+/// Spacing between distance ticks giving a handful of round-numbered ticks
+fn tick_interval(span: f64) -> f64 {
+    let magnitude = 10f64.powf((span / 5.0).log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|interval| span / interval <= 8.0)
+        .unwrap_or(10.0 * magnitude)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bathymetry::Bathymetry;
+    use crate::section;
+
+    #[test]
+    fn threshold_rounds_past_deepest_point() {
+        assert_eq!(next_display_threshold(0.0), 50.0);
+        assert_eq!(next_display_threshold(49.9), 50.0);
+        assert_eq!(next_display_threshold(50.0), 100.0);
+        assert_eq!(next_display_threshold(120.7), 150.0);
+    }
+
+    #[test]
+    fn tick_interval_is_round() {
+        assert_eq!(tick_interval(10.0), 2.0);
+        assert_eq!(tick_interval(35.0), 5.0);
+        assert_eq!(tick_interval(0.9), 0.2);
+    }
+
+    #[test]
+    fn svg_contains_profile_and_labels() {
+        let path = vec![
+            Bathymetry::new(49.24, -122.59, 157.692),
+            Bathymetry::new(49.24, -122.53, 96.996),
+            Bathymetry::new(49.24, -122.46, 107.072),
+        ];
+        let svg = to_svg(&section::section(&path), &PlotOptions::default());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<polygon"));
+        assert!(svg.contains("<polyline"));
+        assert!(svg.contains(">200</text>"));
+        assert!(svg.contains("Distance (km)"));
+        assert!(svg.contains("Depth (m)"));
+    }
+
+    #[test]
+    fn distance_labels_have_the_decimals_of_the_interval() {
+        // about 1.33 km long, ticked every 0.2 km
+        let path = vec![Bathymetry::new(0.0, 0.0, 10.0), Bathymetry::new(0.0, 0.012, 20.0)];
+        let svg = to_svg(&section::section(&path), &PlotOptions::default());
+        assert!(svg.contains(">0.0</text>"));
+        assert!(svg.contains(">0.6</text>"));
+        assert!(svg.contains(">1.2</text>"));
+        assert!(!svg.contains("0000"));
+    }
+
+    #[test]
+    fn svg_of_empty_section_has_axes() {
+        let svg = to_svg(&[], &PlotOptions::default());
+        assert!(!svg.contains("<polygon"));
+        assert!(svg.contains(">0</text>"));
+    }
+
+    #[test]
+    fn png_has_requested_size() {
+        let path = vec![
+            Bathymetry::new(49.24, -122.59, 157.692),
+            Bathymetry::new(49.24, -122.53, 96.996),
+        ];
+        let options = PlotOptions {
+            width: 320,
+            height: 240,
+        };
+        let png = to_png(&section::section(&path), &options).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        // width and height are the first fields of the IHDR chunk
+        assert_eq!(u32::from_be_bytes([png[16], png[17], png[18], png[19]]), 320);
+        assert_eq!(u32::from_be_bytes([png[20], png[21], png[22], png[23]]), 240);
+    }
+
+    #[test]
+    fn labels_use_the_bundled_font() {
+        let options = usvg_options();
+        let query = usvg::fontdb::Query {
+            families: &[usvg::fontdb::Family::SansSerif],
+            ..Default::default()
+        };
+        let id = options.fontdb.query(&query).unwrap();
+        assert_eq!(options.fontdb.face(id).unwrap().family, FONT_FAMILY);
+    }
+}
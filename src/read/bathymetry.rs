@@ -1,47 +1,98 @@
 use crate::bathymetry::Bathymetry;
+use crate::error::Error;
 use crate::parse;
+use crate::read::{self, Parsed, Positive, ReadMode};
 
 use std::collections::HashMap;
-use std::error::Error;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Seek};
+#[cfg(feature = "netcdf")]
+use std::path::Path;
 
-use geo::{Coordinate, Point, Polygon};
-use geo::algorithm::contains::Contains;
+use geo::{MultiPolygon, Rect};
+#[cfg(feature = "netcdf")]
+use geo::algorithm::bounding_rect::BoundingRect;
 
-/// Read bathymetry data from the NONNA-10 ASCII format
-pub fn from_nonna<T: Read>(input: &mut BufReader<T>, bb: &Option<Polygon<f64>>) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
-    let mut out = vec![];
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+/// Read bathymetry data from the NONNA-10 ASCII format, skipping malformed lines
+pub fn from_nonna<T: Read>(input: &mut BufReader<T>, bb: &Option<MultiPolygon<f64>>) -> Result<Vec<Bathymetry>, Error> {
+    from_nonna_with_mode(input, bb, ReadMode::Lenient).map(|parsed| parsed.data)
+}
+
+/// Read bathymetry data from the NONNA-10 ASCII format, reporting malformed lines according to mode
+///
+/// A first line with no numbers in it is taken to be a header and is never reported.
+pub fn from_nonna_with_mode<T: Read>(
+    input: &mut BufReader<T>,
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+) -> Result<Parsed<Bathymetry>, Error> {
+    let bounds = read::Bounds::new(bb);
+    let mut out = Parsed::default();
     let mut buffer = String::new();
+    let mut line = 0;
     loop {
         buffer.clear();
         if input.read_line(&mut buffer)? == 0 {
             break Ok(out);
         }
-        if let Some(value) = nonna_line(&buffer) {
-            // use Option::iter to avoid consuming bb
-            // Iterator::all returns true on empty iterator
-            if bb.iter().all(|b| b.contains(&Coordinate::from(value.point()))) {
-                out.push(value);
-            }
+        line += 1;
+        if buffer.trim().is_empty() {
+            continue;
+        }
+        match nonna_line(&buffer, line) {
+            Ok(value) => out.keep_within(value, &bounds),
+            Err(_) if line == 1 && is_header(&buffer) => {}
+            Err(e) => mode.reject(&mut out.rejected, &buffer, e)?,
         }
     }
 }
 
-fn nonna_line(input: &str) -> Option<Bathymetry> {
+/// Whether a line is made of words rather than values, such as a row of column names
+fn is_header(input: &str) -> bool {
+    !input
+        .split_whitespace()
+        .any(|token| token.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.'))
+}
+
+fn nonna_line(input: &str, line: usize) -> Result<Bathymetry, Error> {
     let mut split = input.split(' ');
-    let latitude = parse::parse_dms_latitude(split.next()?)?;
-    let longitude = parse::parse_dms_longitude(split.next()?)?;
-    let depth = parse::parse_float(split.next()?)?;
-    Some(Bathymetry::new(latitude, longitude, depth))
+    let latitude = split
+        .next()
+        .and_then(parse::parse_dms_latitude)
+        .ok_or_else(|| Error::parse(line, 1, "Invalid latitude"))?;
+    let longitude = split
+        .next()
+        .and_then(parse::parse_dms_longitude)
+        .ok_or_else(|| Error::parse(line, 2, "Invalid longitude"))?;
+    let depth = split
+        .next()
+        .and_then(parse::parse_float)
+        .ok_or_else(|| Error::parse(line, 3, "Invalid depth"))?;
+    Ok(Bathymetry::new(latitude, longitude, depth))
 }
 
-pub fn from_csv<T: Read>(input: &mut BufReader<T>, bb: &Option<Polygon<f64>>) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
-    let mut out = vec![];
+/// Read bathymetry data from a CSV, skipping malformed rows
+pub fn from_csv<T: Read>(input: &mut BufReader<T>, bb: &Option<MultiPolygon<f64>>) -> Result<Vec<Bathymetry>, Error> {
+    from_csv_with_mode(input, bb, ReadMode::Lenient).map(|parsed| parsed.data)
+}
+
+/// Read bathymetry data from a CSV, reporting malformed rows according to mode
+pub fn from_csv_with_mode<T: Read>(
+    input: &mut BufReader<T>,
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+) -> Result<Parsed<Bathymetry>, Error> {
+    let bounds = read::Bounds::new(bb);
+    let mut out = Parsed::default();
     let mut buffer = String::new();
     // read header
     if input.read_line(&mut buffer)? == 0 {
         return Ok(out);
     }
+    let mut line = 1;
     let mut column_map = HashMap::new();
     for (index, item) in buffer.split(',').enumerate() {
         column_map.insert(item.trim().trim_matches('"'), index);
@@ -50,12 +101,12 @@ pub fn from_csv<T: Read>(input: &mut BufReader<T>, bb: &Option<Polygon<f64>>) ->
         .keys()
         .find(|name| name.to_lowercase().starts_with("la"))
         .and_then(|key| column_map.get(key))
-        .ok_or("Latitude not found")?;
+        .ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
     let longitude_index = *column_map
         .keys()
         .find(|name| name.to_lowercase().starts_with("lo"))
         .and_then(|key| column_map.get(key))
-        .ok_or("Longitude not found")?;
+        .ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
     let depth_index = column_map
         .keys()
         .find(|name| name.to_lowercase().starts_with("depth"))
@@ -66,37 +117,504 @@ pub fn from_csv<T: Read>(input: &mut BufReader<T>, bb: &Option<Polygon<f64>>) ->
         .find(|name| name.to_lowercase().starts_with("elevation"))
         .and_then(|key| column_map.get(key))
         .map(|&value| value);
-    if depth_index.is_none() && elevation_index.is_none() {
-        return Err(Box::<dyn Error>::from("Depth not found".to_string()));
-    }
+    // elevation is converted to depth by flipping its sign
+    let (depth_index, sign) = match (depth_index, elevation_index) {
+        (Some(index), _) => (index, 1.0),
+        (None, Some(index)) => (index, -1.0),
+        (None, None) => return Err(Error::MissingColumn("Depth".to_string())),
+    };
     loop {
         buffer.clear();
         if input.read_line(&mut buffer)? == 0 {
             break Ok(out);
         }
+        line += 1;
+        if buffer.trim().is_empty() {
+            continue;
+        }
         let row: Vec<&str> = buffer.split(',').collect();
-        let latitude = parse::parse_float(row[latitude_index]);
-        let longitude = parse::parse_float(row[longitude_index]);
-        let depth = if let Some(index) = depth_index {
-            parse::parse_float(row[index])
-        } else if let Some(index) = elevation_index {
-            parse::parse_float(row[index]).map(|value| value * -1.0)
-        } else {
-            unreachable!()
+        let field = |index: usize, name: &str| {
+            parse::parse_float(read::csv_field(&row, index, line)?)
+                .ok_or_else(|| Error::parse(line, index + 1, format!("Invalid {}", name)))
         };
-        if let Some(((lat, lon), dep)) = latitude.zip(longitude).zip(depth) {
-            // use Option::iter to avoid consuming bb
-            // Iterator::all returns true on empty iterator
-            if bb.iter().all(|b| b.contains(&Point::new(lon, lat))) {
-                out.push(Bathymetry::new(lat, lon, dep));
+        let value = field(latitude_index, "latitude").and_then(|lat| {
+            let lon = field(longitude_index, "longitude")?;
+            let dep = field(depth_index, "depth")?;
+            Ok((lat, lon, dep * sign))
+        });
+        match value {
+            Ok((lat, lon, dep)) => out.keep_within(Bathymetry::new(lat, lon, dep), &bounds),
+            Err(e) => mode.reject(&mut out.rejected, &buffer, e)?,
+        }
+    }
+}
+
+/// Read bathymetry data from an ESRI ASCII grid, with cell values as depths or, positive up, elevations
+pub fn from_esri_ascii<T: Read>(
+    input: &mut BufReader<T>,
+    bb: &Option<MultiPolygon<f64>>,
+    positive: Positive,
+) -> Result<Vec<Bathymetry>, Error> {
+    let mut buffer = String::new();
+    let (grid, line) = esri_ascii_header(input, &mut buffer)?;
+    input.read_to_string(&mut buffer)?;
+    let AsciiGrid {
+        ncols,
+        nrows,
+        dx,
+        dy,
+        west,
+        south,
+        nodata,
+    } = grid;
+
+    let bounds = read::Bounds::new(bb);
+    let mut out = vec![];
+    let mut index = 0;
+    // the header was checked to give a number of cells that fits
+    let cells = ncols * nrows;
+    // rows may wrap across lines, so values are counted across the whole grid
+    for (offset, text) in buffer.lines().enumerate() {
+        for (column, value) in text.split_whitespace().enumerate() {
+            if index >= cells {
+                index += 1;
+                continue;
+            }
+            let value = parse::parse_float(value)
+                .ok_or_else(|| Error::parse(line + offset, column + 1, "Invalid grid value"))?;
+            let (row, col) = (index / ncols, index % ncols);
+            index += 1;
+            if nodata == Some(value) {
+                continue;
+            }
+            let depth = positive.depth(value);
+            // rows are stored from north to south
+            let lon = west + col as f64 * dx;
+            let lat = south + (nrows - row - 1) as f64 * dy;
+            if bounds.contains((lon, lat)) {
+                out.push(Bathymetry::new(lat, lon, depth));
+            }
+        }
+    }
+    if index != cells {
+        return Err(Error::InvalidInput(format!(
+            "ESRI ASCII grid has {} values for {} columns and {} rows",
+            index, ncols, nrows
+        )));
+    }
+    Ok(out)
+}
+
+/// Extent of the cell centres of an ESRI ASCII grid, read from its header alone
+pub fn esri_ascii_extent<T: Read>(input: &mut BufReader<T>) -> Result<Rect<f64>, Error> {
+    let (grid, _) = esri_ascii_header(input, &mut String::new())?;
+    let east = grid.west + grid.ncols.saturating_sub(1) as f64 * grid.dx;
+    let north = grid.south + grid.nrows.saturating_sub(1) as f64 * grid.dy;
+    Ok(Rect::new((grid.west, grid.south), (east, north)))
+}
+
+/// Layout of an ESRI ASCII grid, with the lower left cell given by its centre
+struct AsciiGrid {
+    ncols: usize,
+    nrows: usize,
+    dx: f64,
+    dy: f64,
+    west: f64,
+    south: f64,
+    nodata: Option<f64>,
+}
+
+/// Read the header, leaving the first line of the grid in `buffer` and returning its line number
+fn esri_ascii_header<T: Read>(input: &mut BufReader<T>, buffer: &mut String) -> Result<(AsciiGrid, usize), Error> {
+    let mut header = HashMap::new();
+    let mut line = 0;
+    // header lines start with a keyword, the grid starts on the first line that does not
+    loop {
+        buffer.clear();
+        if input.read_line(buffer)? == 0 {
+            break;
+        }
+        line += 1;
+        let mut split = buffer.split_whitespace();
+        match split.next() {
+            Some(key) if key.starts_with(char::is_alphabetic) => {
+                let value = split
+                    .next()
+                    .and_then(parse::parse_float)
+                    .ok_or_else(|| Error::parse(line, 2, format!("Invalid value for {}", key)))?;
+                header.insert(key.to_lowercase(), value);
+            }
+            Some(_) => break,
+            None => continue,
+        }
+    }
+
+    let header_value = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| Error::InvalidInput(format!("ESRI ASCII header is missing {}", key)))
+    };
+    let dx = header_value("dx").or_else(|_| header_value("cellsize"))?;
+    let dy = header_value("dy").or_else(|_| header_value("cellsize"))?;
+    // corner registration references the outer edge of the lower left cell rather than its centre
+    let (west, south) = match (header.get("xllcenter"), header.get("yllcenter")) {
+        (Some(&x), Some(&y)) => (x, y),
+        _ => (header_value("xllcorner")? + dx / 2.0, header_value("yllcorner")? + dy / 2.0),
+    };
+    let count = |key: &str| {
+        let value = header_value(key)?;
+        if value >= 1.0 && value.fract() == 0.0 && value < usize::MAX as f64 {
+            Ok(value as usize)
+        } else {
+            Err(Error::InvalidInput(format!("ESRI ASCII {} must be a positive whole number", key)))
+        }
+    };
+    let (ncols, nrows) = (count("ncols")?, count("nrows")?);
+    if ncols.checked_mul(nrows).is_none() {
+        return Err(Error::InvalidInput("ESRI ASCII grid has too many cells".to_string()));
+    }
+    let grid = AsciiGrid {
+        ncols,
+        nrows,
+        dx,
+        dy,
+        west,
+        south,
+        nodata: header.get("nodata_value").copied(),
+    };
+    Ok((grid, line))
+}
+
+/// Read bathymetry data from a whitespace-separated longitude/latitude/depth point cloud, skipping malformed lines
+pub fn from_xyz<T: Read>(input: &mut BufReader<T>, bb: &Option<MultiPolygon<f64>>) -> Result<Vec<Bathymetry>, Error> {
+    from_xyz_with_mode(input, bb, ReadMode::Lenient).map(|parsed| parsed.data)
+}
+
+/// Read bathymetry data from an XYZ point cloud, reporting malformed lines according to mode
+///
+/// A first line with no numbers in it is taken to be a header and is never reported.
+pub fn from_xyz_with_mode<T: Read>(
+    input: &mut BufReader<T>,
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+) -> Result<Parsed<Bathymetry>, Error> {
+    let bounds = read::Bounds::new(bb);
+    let mut out = Parsed::default();
+    let mut buffer = String::new();
+    let mut line = 0;
+    loop {
+        buffer.clear();
+        if input.read_line(&mut buffer)? == 0 {
+            break Ok(out);
+        }
+        line += 1;
+        if buffer.trim().is_empty() {
+            continue;
+        }
+        match xyz_line(&buffer, line) {
+            Ok(value) => out.keep_within(value, &bounds),
+            Err(_) if line == 1 && is_header(&buffer) => {}
+            Err(e) => mode.reject(&mut out.rejected, &buffer, e)?,
+        }
+    }
+}
+
+fn xyz_line(input: &str, line: usize) -> Result<Bathymetry, Error> {
+    let mut split = input.split_whitespace();
+    let longitude = split
+        .next()
+        .and_then(parse::parse_float)
+        .ok_or_else(|| Error::parse(line, 1, "Invalid longitude"))?;
+    let latitude = split
+        .next()
+        .and_then(parse::parse_float)
+        .ok_or_else(|| Error::parse(line, 2, "Invalid latitude"))?;
+    let depth = split
+        .next()
+        .and_then(parse::parse_float)
+        .ok_or_else(|| Error::parse(line, 3, "Invalid depth"))?;
+    Ok(Bathymetry::new(latitude, longitude, depth))
+}
+
+/// Read bathymetry data from a CF-convention NetCDF grid
+///
+/// Only the part of the grid covering the bounding box is read from disk.
+#[cfg(feature = "netcdf")]
+pub fn from_netcdf<P: AsRef<Path>>(path: P, bb: &Option<MultiPolygon<f64>>) -> Result<Vec<Bathymetry>, Error> {
+    let file = netcdf::open(path)?;
+    let (latitude, longitude) = netcdf_coordinates(&file)?;
+    let lat_dim = latitude.dimensions()[0].name();
+    let lon_dim = longitude.dimensions()[0].name();
+
+    let (variable, sign) = file
+        .variables()
+        .filter(|v| {
+            let dims: Vec<String> = v.dimensions().iter().map(|d| d.name()).collect();
+            dims.len() == 2 && dims.contains(&lat_dim) && dims.contains(&lon_dim)
+        })
+        .find_map(|v| depth_sign(&v).map(|sign| (v, sign)))
+        .ok_or_else(|| Error::MissingColumn("Depth".to_string()))?;
+    let lat_first = variable.dimensions()[0].name() == lat_dim;
+
+    let lats: Vec<f64> = latitude.get_values(..)?;
+    let lons: Vec<f64> = longitude.get_values(..)?;
+    let envelope = bb.as_ref().and_then(|b| b.bounding_rect());
+    let lat_range = index_range(&lats, envelope.map(|r| (r.min().y, r.max().y)));
+    let lon_range = index_range(&lons, envelope.map(|r| (r.min().x, r.max().x)));
+    if lat_range.is_empty() || lon_range.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let values: Vec<f64> = if lat_first {
+        variable.get_values((lat_range.clone(), lon_range.clone()))?
+    } else {
+        variable.get_values((lon_range.clone(), lat_range.clone()))?
+    };
+    let attribute = |name: &str| -> Result<Option<f64>, Error> {
+        match variable.attribute_value(name) {
+            Some(value) => Ok(Some(value?.try_into()?)),
+            None => Ok(None),
+        }
+    };
+    let fill = attribute("_FillValue")?;
+    let missing = attribute("missing_value")?;
+    let scale = attribute("scale_factor")?.unwrap_or(1.0);
+    let offset = attribute("add_offset")?.unwrap_or(0.0);
+
+    let bounds = read::Bounds::new(bb);
+    let mut out = vec![];
+    for (i, &lat) in lats[lat_range.clone()].iter().enumerate() {
+        for (j, &lon) in lons[lon_range.clone()].iter().enumerate() {
+            let raw = if lat_first {
+                values[i * lon_range.len() + j]
+            } else {
+                values[j * lat_range.len() + i]
+            };
+            if raw.is_nan() || fill == Some(raw) || missing == Some(raw) {
+                continue;
+            }
+            if bounds.contains((lon, lat)) {
+                out.push(Bathymetry::new(lat, lon, sign * (raw * scale + offset)));
             }
         }
     }
+    Ok(out)
+}
+
+/// Extent of a NetCDF grid from its coordinate variables, without reading the grid itself
+#[cfg(feature = "netcdf")]
+pub fn netcdf_extent<P: AsRef<Path>>(path: P) -> Result<Rect<f64>, Error> {
+    let file = netcdf::open(path)?;
+    let (latitude, longitude) = netcdf_coordinates(&file)?;
+    let lats: Vec<f64> = latitude.get_values(..)?;
+    let lons: Vec<f64> = longitude.get_values(..)?;
+    let span = |values: &[f64]| -> Option<(f64, f64)> {
+        values.iter().fold(None, |span, &value| match span {
+            Some((min, max)) => Some((f64::min(min, value), f64::max(max, value))),
+            None => Some((value, value)),
+        })
+    };
+    match (span(&lons), span(&lats)) {
+        (Some((west, east)), Some((south, north))) => Ok(Rect::new((west, south), (east, north))),
+        _ => Err(Error::InvalidInput("NetCDF grid has no coordinates".to_string())),
+    }
+}
+
+/// Latitude and longitude coordinate variables of a NetCDF grid
+#[cfg(feature = "netcdf")]
+fn netcdf_coordinates(file: &netcdf::File) -> Result<(netcdf::Variable<'_>, netcdf::Variable<'_>), Error> {
+    let latitude = file
+        .variables()
+        .find(|v| is_coordinate(v, "latitude", "degrees_north", &["lat", "latitude", "y"]))
+        .ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
+    let longitude = file
+        .variables()
+        .find(|v| is_coordinate(v, "longitude", "degrees_east", &["lon", "longitude", "x"]))
+        .ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
+    Ok((latitude, longitude))
+}
+
+#[cfg(feature = "netcdf")]
+fn is_coordinate(variable: &netcdf::Variable, standard_name: &str, units: &str, names: &[&str]) -> bool {
+    if variable.dimensions().len() != 1 {
+        return false;
+    }
+    text_attribute(variable, "standard_name").as_deref() == Some(standard_name)
+        || text_attribute(variable, "units").as_deref() == Some(units)
+        || names.contains(&variable.name().to_lowercase().as_str())
+}
+
+/// Multiplier converting the variable into positive-down depth, if it represents one
+#[cfg(feature = "netcdf")]
+fn depth_sign(variable: &netcdf::Variable) -> Option<f64> {
+    match text_attribute(variable, "positive").as_deref() {
+        Some("down") => return Some(1.0),
+        Some("up") => return Some(-1.0),
+        _ => {}
+    }
+    let standard_name = text_attribute(variable, "standard_name").unwrap_or_default();
+    let name = variable.name().to_lowercase();
+    if standard_name == "sea_floor_depth_below_sea_level" || standard_name == "depth" || name == "depth" {
+        Some(1.0)
+    } else if standard_name == "height_above_mean_sea_level"
+        || standard_name == "height_above_reference_ellipsoid"
+        || standard_name == "altitude"
+        || name == "elevation"
+        || name == "z"
+    {
+        Some(-1.0)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "netcdf")]
+fn text_attribute(variable: &netcdf::Variable, name: &str) -> Option<String> {
+    match variable.attribute_value(name) {
+        Some(Ok(netcdf::AttributeValue::Str(value))) => Some(value.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// Smallest contiguous index range covering the coordinates within bounds
+#[cfg(feature = "netcdf")]
+fn index_range(coordinates: &[f64], bounds: Option<(f64, f64)>) -> std::ops::Range<usize> {
+    let (min, max) = match bounds {
+        Some(bounds) => bounds,
+        None => return 0..coordinates.len(),
+    };
+    let inside = |c: &f64| (min..=max).contains(c);
+    match (coordinates.iter().position(inside), coordinates.iter().rposition(inside)) {
+        (Some(first), Some(last)) => first..last + 1,
+        _ => 0..0,
+    }
+}
+
+/// Read bathymetry data from a single-band GeoTIFF depth grid
+pub fn from_geotiff<T: Read + Seek>(input: &mut BufReader<T>, bb: &Option<MultiPolygon<f64>>) -> Result<Vec<Bathymetry>, Error> {
+    let mut decoder = Decoder::new(input)?;
+    if !matches!(decoder.colortype()?, ColorType::Gray(_)) {
+        return Err(Error::InvalidInput("GeoTIFF must contain a single band".to_string()));
+    }
+    let (width, height) = decoder.dimensions()?;
+    let transform = GeoTransform::from_decoder(&mut decoder)?;
+    let nodata = match decoder.find_tag(Tag::GdalNodata)? {
+        Some(value) => parse::parse_float(value.into_string()?.trim_end_matches('\0')),
+        None => None,
+    };
+    let values: Vec<f64> = match decoder.read_image()? {
+        DecodingResult::U8(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U64(v) => v.into_iter().map(|d| d as f64).collect(),
+        DecodingResult::F32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(v) => v,
+        DecodingResult::I8(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I64(v) => v.into_iter().map(|d| d as f64).collect(),
+    };
+
+    let bounds = read::Bounds::new(bb);
+    let mut out = vec![];
+    for row in 0..height as usize {
+        for col in 0..width as usize {
+            let depth = values[row * width as usize + col];
+            if depth.is_nan() || nodata == Some(depth) {
+                continue;
+            }
+            let (lon, lat) = transform.apply(col as f64, row as f64);
+            if bounds.contains((lon, lat)) {
+                out.push(Bathymetry::new(lat, lon, depth));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Extent of the pixel centres of a GeoTIFF, read from its tags without decoding the image
+pub fn geotiff_extent<T: Read + Seek>(input: &mut BufReader<T>) -> Result<Rect<f64>, Error> {
+    let mut decoder = Decoder::new(input)?;
+    let (width, height) = decoder.dimensions()?;
+    let transform = GeoTransform::from_decoder(&mut decoder)?;
+    let (right, bottom) = (width.saturating_sub(1) as f64, height.saturating_sub(1) as f64);
+    let corners = [(0.0, 0.0), (right, 0.0), (0.0, bottom), (right, bottom)].map(|(col, row)| transform.apply(col, row));
+    let (mut min, mut max) = (corners[0], corners[0]);
+    for (lon, lat) in corners {
+        min = (min.0.min(lon), min.1.min(lat));
+        max = (max.0.max(lon), max.1.max(lat));
+    }
+    Ok(Rect::new(min, max))
+}
+
+// GeoKey describing whether a pixel value represents an area or a point
+const RASTER_TYPE_GEO_KEY: u16 = 1025;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// Affine mapping from raster (column, row) to (longitude, latitude)
+struct GeoTransform {
+    // x = a * col + b * row + c, y = d * col + e * row + f
+    coefficients: [f64; 6],
+}
+
+impl GeoTransform {
+    fn from_decoder<T: Read + Seek>(decoder: &mut Decoder<T>) -> Result<Self, Error> {
+        let coefficients = if let Some(matrix) = decoder.find_tag(Tag::ModelTransformationTag)? {
+            let m = matrix.into_f64_vec()?;
+            if m.len() < 8 {
+                return Err(Error::InvalidInput("Invalid ModelTransformationTag".to_string()));
+            }
+            [m[0], m[1], m[3], m[4], m[5], m[7]]
+        } else {
+            let scale = decoder
+                .find_tag(Tag::ModelPixelScaleTag)?
+                .ok_or_else(|| Error::InvalidInput("GeoTIFF is missing ModelPixelScaleTag".to_string()))?
+                .into_f64_vec()?;
+            let tiepoint = decoder
+                .find_tag(Tag::ModelTiepointTag)?
+                .ok_or_else(|| Error::InvalidInput("GeoTIFF is missing ModelTiepointTag".to_string()))?
+                .into_f64_vec()?;
+            if scale.len() < 2 || tiepoint.len() < 6 {
+                return Err(Error::InvalidInput("Invalid GeoTIFF tie point or pixel scale".to_string()));
+            }
+            let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+            // rows increase southward, so the y scale is negated
+            [scale[0], 0.0, x - i * scale[0], 0.0, -scale[1], y + j * scale[1]]
+        };
+        let mut transform = Self { coefficients };
+        if !Self::pixel_is_point(decoder)? {
+            // PixelIsArea georeferences the corner of each pixel, so shift to its centre
+            let [a, b, c, d, e, f] = transform.coefficients;
+            transform.coefficients = [a, b, c + 0.5 * (a + b), d, e, f + 0.5 * (d + e)];
+        }
+        Ok(transform)
+    }
+
+    fn pixel_is_point<T: Read + Seek>(decoder: &mut Decoder<T>) -> Result<bool, Error> {
+        let directory = match decoder.find_tag(Tag::GeoKeyDirectoryTag)? {
+            Some(value) => value.into_u16_vec()?,
+            None => return Ok(false),
+        };
+        // header is four shorts, followed by (key, location, count, value) entries
+        Ok(directory
+            .chunks_exact(4)
+            .skip(1)
+            .any(|entry| entry[0] == RASTER_TYPE_GEO_KEY && entry[1] == 0 && entry[3] == RASTER_PIXEL_IS_POINT))
+    }
+
+    fn apply(&self, col: f64, row: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.coefficients;
+        (a * col + b * row + c, d * col + e * row + f)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::read::Rejected;
+
+    use std::io::Cursor;
 
     #[test]
     fn reads_bathymetry_lines() {
@@ -150,4 +668,306 @@ mod tests {
         assert!(actual.is_ok());
         assert_eq!(actual.unwrap(), expected);
     }
+
+    #[test]
+    fn nonna_line_reports_position_of_failure() {
+        let actual = nonna_line("0-0-0.0N not-a-longitude 0.0", 7);
+        assert!(matches!(actual, Err(Error::Parse { line: 7, column: 2, .. })));
+    }
+
+    #[test]
+    fn rejects_short_csv_rows_in_strict_mode() {
+        let source = "longitude,latitude,depth\n-123.456,49.58,100.0\n-123.456";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_csv_with_mode(&mut reader, &None, ReadMode::Strict);
+        assert!(matches!(actual, Err(Error::Parse { line: 3, column: 2, .. })));
+    }
+
+    #[test]
+    fn reports_malformed_csv_rows_in_lenient_mode() {
+        let source = "longitude,latitude,depth\n-123.456,49.58,100.0\n-123.456\n-123.456,north,100.0";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_csv_with_mode(&mut reader, &None, ReadMode::Lenient);
+        let expected = Parsed {
+            data: vec![Bathymetry::new(49.58, -123.456, 100.0)],
+            rejected: vec![
+                Rejected {
+                    line: 3,
+                    column: 2,
+                    text: "-123.456".to_string(),
+                    reason: "Missing value".to_string(),
+                },
+                Rejected {
+                    line: 4,
+                    column: 2,
+                    text: "-123.456,north,100.0".to_string(),
+                    reason: "Invalid latitude".to_string(),
+                },
+            ],
+            extent: Some(Rect::new((-123.456, 49.58), (-123.456, 49.58))),
+        };
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_malformed_nonna_lines_in_strict_mode() {
+        let source = "\"Lat (DMS)\" \"Long (DMS)\" \"Depth (m)\"\n0-0-0.0N 0-0-0.0E 0.0\n0-0-0.0N 0-0-0.0E deep";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_nonna_with_mode(&mut reader, &None, ReadMode::Strict);
+        assert!(matches!(actual, Err(Error::Parse { line: 3, column: 3, .. })));
+    }
+
+    #[test]
+    fn rejects_malformed_first_nonna_line_in_strict_mode() {
+        let source = "0-0-0.0N 0-0-0.0E deep\n0-0-0.0N 0-0-0.0E 0.0";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_nonna_with_mode(&mut reader, &None, ReadMode::Strict);
+        assert!(matches!(actual, Err(Error::Parse { line: 1, column: 3, .. })));
+    }
+
+    #[test]
+    fn reports_malformed_first_xyz_line_in_lenient_mode() {
+        let source = "-123.456 49.58\n-123.456 49.59 110.0";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_xyz_with_mode(&mut reader, &None, ReadMode::Lenient).unwrap();
+        assert_eq!(actual.data, vec![Bathymetry::new(49.59, -123.456, 110.0)]);
+        assert_eq!(actual.rejected.len(), 1);
+        assert_eq!(actual.rejected[0].line, 1);
+    }
+
+    #[test]
+    fn reports_malformed_nonna_lines_in_lenient_mode() {
+        let source = "header\n0-0-0.0N 0-0-0.0E 0.0\nnot bathymetry\n";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_nonna_with_mode(&mut reader, &None, ReadMode::Lenient);
+        let expected = Parsed {
+            data: vec![Bathymetry::new(0.0, 0.0, 0.0)],
+            rejected: vec![Rejected {
+                line: 3,
+                column: 1,
+                text: "not bathymetry".to_string(),
+                reason: "Invalid latitude".to_string(),
+            }],
+            extent: Some(Rect::new((0.0, 0.0), (0.0, 0.0))),
+        };
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_csv_without_depth() {
+        let source = "longitude,latitude\n-123.456,49.58";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_csv(&mut reader, &None);
+        assert!(matches!(actual, Err(Error::MissingColumn(_))));
+    }
+
+    #[test]
+    fn reads_bathymetry_from_esri_ascii_corner() {
+        let source = concat!(
+            "ncols 2\n",
+            "nrows 2\n",
+            "xllcorner -123.0\n",
+            "yllcorner 49.0\n",
+            "cellsize 0.5\n",
+            "NODATA_value -9999\n",
+            "10.0 20.0\n",
+            "-9999 40.0\n",
+        );
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_esri_ascii(&mut reader, &None, Positive::Down);
+        let expected = vec![
+            Bathymetry::new(49.75, -122.75, 10.0),
+            Bathymetry::new(49.75, -122.25, 20.0),
+            Bathymetry::new(49.25, -122.25, 40.0),
+        ];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_bathymetry_from_esri_ascii_center() {
+        let source = "ncols 2\nnrows 1\nxllcenter -123.0\nyllcenter 49.0\ncellsize 0.5\n10.0 20.0\n";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_esri_ascii(&mut reader, &None, Positive::Down);
+        let expected = vec![
+            Bathymetry::new(49.0, -123.0, 10.0),
+            Bathymetry::new(49.0, -122.5, 20.0),
+        ];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_elevations_from_esri_ascii_positive_up() {
+        let source = "ncols 3\nnrows 1\nxllcenter -123.0\nyllcenter 49.0\ncellsize 0.5\nNODATA_value -9999\n-10.0 -9999 5.0\n";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_esri_ascii(&mut reader, &None, Positive::Up);
+        let expected = vec![
+            Bathymetry::new(49.0, -123.0, 10.0),
+            Bathymetry::new(49.0, -122.0, -5.0),
+        ];
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_esri_ascii_without_header() {
+        let source = "10.0 20.0\n";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_esri_ascii(&mut reader, &None, Positive::Down);
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn rejects_esri_ascii_with_the_wrong_number_of_values() {
+        let header = "ncols 2\nnrows 2\nxllcenter -123.0\nyllcenter 49.0\ncellsize 0.5\n";
+        for values in ["10.0 20.0\n40.0\n", "10.0 20.0\n30.0 40.0\n50.0\n"] {
+            let source = format!("{}{}", header, values);
+            let mut reader = BufReader::new(source.as_bytes());
+            let actual = from_esri_ascii(&mut reader, &None, Positive::Down);
+            assert!(matches!(actual, Err(Error::InvalidInput(_))), "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn rejects_esri_ascii_with_unusable_counts() {
+        for counts in ["ncols 2.5\nnrows 1", "ncols -2\nnrows 1", "ncols nan\nnrows 1", "ncols 1e19\nnrows 1e19"] {
+            let source = format!("{}\nxllcenter -123.0\nyllcenter 49.0\ncellsize 0.5\n10.0 20.0\n", counts);
+            let mut reader = BufReader::new(source.as_bytes());
+            let actual = from_esri_ascii(&mut reader, &None, Positive::Down);
+            assert!(matches!(actual, Err(Error::InvalidInput(_))), "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn reads_bathymetry_from_xyz() {
+        let source = "-123.456 49.58 100.0\n-123.456\t49.59  110.0\nnot bathymetry";
+        let mut reader = BufReader::new(source.as_bytes());
+        let actual = from_xyz(&mut reader, &None);
+        let expected = vec![
+            Bathymetry::new(49.58, -123.456, 100.0),
+            Bathymetry::new(49.59, -123.456, 110.0),
+        ];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn xyz_respects_bounding_box() {
+        let source = "-123.456 49.58 100.0\n-124.456 49.58 100.0";
+        let mut reader = BufReader::new(source.as_bytes());
+        let bb = parse::parse_bounding_box("-124.0,49.0,-123.0,50.0").map(MultiPolygon::from);
+        let actual = from_xyz(&mut reader, &bb);
+        let expected = vec![Bathymetry::new(49.58, -123.456, 100.0)];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn csv_respects_area_with_islands() {
+        let source = "latitude,longitude,depth\n49.1,-123.9,10.0\n49.5,-123.5,20.0\n49.9,-123.1,30.0\n49.5,-122.5,40.0";
+        let mut reader = BufReader::new(source.as_bytes());
+        let area = "MULTIPOLYGON (((-124 49, -123 49, -123 50, -124 50, -124 49), (-123.6 49.4, -123.4 49.4, -123.4 49.6, -123.6 49.6, -123.6 49.4)), ((-122.6 49.4, -122.4 49.4, -122.4 49.6, -122.6 49.4)))";
+        let area = read::polygon::from_wkt(&mut BufReader::new(area.as_bytes())).unwrap();
+        let actual = from_csv(&mut reader, &Some(area)).unwrap();
+        let expected = vec![
+            Bathymetry::new(49.1, -123.9, 10.0),
+            Bathymetry::new(49.9, -123.1, 30.0),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "netcdf")]
+    #[test]
+    fn reads_bathymetry_subset_from_netcdf() {
+        let path = std::env::temp_dir().join("thalweg-reads-bathymetry-subset-from-netcdf.nc");
+        {
+            let mut file = netcdf::create(&path).unwrap();
+            file.add_dimension("lat", 2).unwrap();
+            file.add_dimension("lon", 3).unwrap();
+            {
+                let mut lat = file.add_variable::<f64>("lat", &["lat"]).unwrap();
+                lat.put_attribute("standard_name", "latitude").unwrap();
+                lat.put_values(&[49.0, 49.5], ..).unwrap();
+            }
+            {
+                let mut lon = file.add_variable::<f64>("lon", &["lon"]).unwrap();
+                lon.put_attribute("units", "degrees_east").unwrap();
+                lon.put_values(&[-123.0, -122.5, -122.0], ..).unwrap();
+            }
+            {
+                let mut elevation = file.add_variable::<f32>("elevation", &["lat", "lon"]).unwrap();
+                elevation.put_attribute("positive", "up").unwrap();
+                elevation.put_values(&[-10.0f32, -20.0, -30.0, -40.0, -50.0, -60.0], ..).unwrap();
+            }
+        }
+        let bb = parse::parse_bounding_box("-123.1,48.9,-122.4,49.1").map(MultiPolygon::from);
+        let actual = from_netcdf(&path, &bb);
+        let expected = vec![
+            Bathymetry::new(49.0, -123.0, 10.0),
+            Bathymetry::new(49.0, -122.5, 20.0),
+        ];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+        assert_eq!(netcdf_extent(&path).unwrap(), Rect::new((-123.0, 49.0), (-122.0, 49.5)));
+    }
+
+    fn geotiff_source(geokeys: &[u16], nodata: &str) -> Vec<u8> {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let mut buffer = Cursor::new(vec![]);
+        {
+            let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+            let mut image = encoder.new_image::<colortype::Gray32Float>(2, 2).unwrap();
+            let dir = image.encoder();
+            dir.write_tag(Tag::ModelPixelScaleTag, &[0.5, 0.5, 0.0][..]).unwrap();
+            dir.write_tag(Tag::ModelTiepointTag, &[0.0, 0.0, 0.0, -123.0, 49.0, 0.0][..]).unwrap();
+            dir.write_tag(Tag::GeoKeyDirectoryTag, geokeys).unwrap();
+            dir.write_tag(Tag::GdalNodata, nodata).unwrap();
+            image.write_data(&[10.0, 20.0, -9999.0, 40.0]).unwrap();
+        }
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn reads_bathymetry_from_geotiff() {
+        let source = geotiff_source(&[1, 1, 0, 1, 1025, 0, 1, 2], "-9999");
+        let mut reader = BufReader::new(Cursor::new(source));
+        let actual = from_geotiff(&mut reader, &None);
+        let expected = vec![
+            Bathymetry::new(49.0, -123.0, 10.0),
+            Bathymetry::new(49.0, -122.5, 20.0),
+            Bathymetry::new(48.5, -122.5, 40.0),
+        ];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_geotiff_pixel_centres_for_area_rasters() {
+        let source = geotiff_source(&[1, 1, 0, 1, 1025, 0, 1, 1], "-9999");
+        let mut reader = BufReader::new(Cursor::new(source));
+        let actual = from_geotiff(&mut reader, &None);
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap()[0], Bathymetry::new(48.75, -122.75, 10.0));
+    }
+
+    #[test]
+    fn geotiff_respects_bounding_box() {
+        let source = geotiff_source(&[1, 1, 0, 1, 1025, 0, 1, 2], "-9999");
+        let mut reader = BufReader::new(Cursor::new(source));
+        let bb = parse::parse_bounding_box("-123.1,48.9,-122.9,49.1").map(MultiPolygon::from);
+        let actual = from_geotiff(&mut reader, &bb);
+        let expected = vec![Bathymetry::new(49.0, -123.0, 10.0)];
+        assert!(actual.is_ok());
+        assert_eq!(actual.unwrap(), expected);
+    }
+
+    #[test]
+    fn geotiff_extent_covers_pixel_centres() {
+        let source = geotiff_source(&[1, 1, 0, 1, 1025, 0, 1, 1], "-9999");
+        let extent = geotiff_extent(&mut BufReader::new(Cursor::new(source))).unwrap();
+        assert_eq!(extent, Rect::new((-122.75, 48.25), (-122.25, 48.75)));
+    }
 }
@@ -0,0 +1,169 @@
+use crate::bathymetry::Bathymetry;
+use crate::cache::Stamp;
+use crate::error::Error;
+use crate::read::bathymetry;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use geo::Rect;
+
+/// Name of the file kept beside the bathymetry cache to record the extents of data files
+pub const SIDECAR_NAME: &str = ".thwextents";
+
+/// Extents of data files learned from earlier reads, each valid while the file keeps its size and modification time
+#[derive(Debug, Default)]
+pub struct Extents {
+    entries: HashMap<PathBuf, (Stamp, Rect<f64>)>,
+    changed: bool,
+}
+
+impl Extents {
+    /// Read a sidecar file written by `save`, starting afresh if there is none
+    ///
+    /// Each line holds the size, modification time and extent of a file, followed by its path.
+    /// Lines that cannot be understood are ignored, as the extents are always learned again.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let entries = contents.lines().filter_map(parse_line).collect();
+        Ok(Self {
+            entries,
+            changed: false,
+        })
+    }
+
+    /// Write the extents, if any were learned since they were loaded
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        if !self.changed {
+            return Ok(());
+        }
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let mut out = String::new();
+        for (path, (stamp, extent)) in entries {
+            out += format!(
+                "{} {} {} {} {} {} {} {}\n",
+                stamp.size,
+                stamp.seconds,
+                stamp.nanos,
+                extent.min().x,
+                extent.min().y,
+                extent.max().x,
+                extent.max().y,
+                path.display()
+            )
+            .as_str();
+        }
+        File::create(path)?.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    /// Extent of a file as it is now, if it was recorded since the file last changed
+    pub fn get(&self, path: &Path) -> Option<Rect<f64>> {
+        let (stamp, extent) = self.entries.get(path)?;
+        (Stamp::of(path).ok()? == *stamp).then_some(*extent)
+    }
+
+    /// Record the extent of a file as it is now
+    pub fn insert(&mut self, path: PathBuf, extent: Rect<f64>) -> Result<(), Error> {
+        let stamp = Stamp::of(&path)?;
+        self.entries.insert(path, (stamp, extent));
+        self.changed = true;
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Option<(PathBuf, (Stamp, Rect<f64>))> {
+    let mut split = line.splitn(8, ' ');
+    let mut next = || split.next();
+    let stamp = Stamp {
+        size: next()?.parse().ok()?,
+        seconds: next()?.parse().ok()?,
+        nanos: next()?.parse().ok()?,
+    };
+    let mut coordinate = || next()?.parse::<f64>().ok();
+    let min = (coordinate()?, coordinate()?);
+    let max = (coordinate()?, coordinate()?);
+    let path = PathBuf::from(split.next()?);
+    Some((path, (stamp, Rect::new(min, max))))
+}
+
+/// Extent of a file from its header, for the formats that have one
+pub fn from_header(path: &Path) -> Result<Option<Rect<f64>>, Error> {
+    let reader = || -> Result<BufReader<File>, Error> { Ok(BufReader::new(File::open(path)?)) };
+    let extent = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("asc") => bathymetry::esri_ascii_extent(&mut reader()?),
+        Some("tif") | Some("tiff") => bathymetry::geotiff_extent(&mut reader()?),
+        #[cfg(feature = "netcdf")]
+        Some("nc") => bathymetry::netcdf_extent(path),
+        _ => return Ok(None),
+    };
+    extent.map(Some).map_err(|e| e.with_file(path))
+}
+
+/// Smallest rectangle containing every point
+pub fn envelope(points: &[Bathymetry]) -> Option<Rect<f64>> {
+    let first = points.first()?.point();
+    let (min, max) = points.iter().map(Bathymetry::point).fold((first, first), |(min, max), (lon, lat)| {
+        ((min.0.min(lon), min.1.min(lat)), (max.0.max(lon), max.1.max(lat)))
+    });
+    Some(Rect::new(min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extents_round_trip_until_the_file_changes() {
+        let directory = std::env::temp_dir().join("thalweg-extents-round-trip");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let tile = directory.join("tile with spaces.txt");
+        fs::write(&tile, "49-00-00.0N 123-00-00.0W 10.0\n").unwrap();
+        let sidecar = directory.join(SIDECAR_NAME);
+
+        let mut extents = Extents::load(&sidecar).unwrap();
+        assert_eq!(extents.get(&tile), None);
+        let extent = Rect::new((-123.5, 49.0), (-123.0, 49.25));
+        extents.insert(tile.clone(), extent).unwrap();
+        extents.save(&sidecar).unwrap();
+
+        let extents = Extents::load(&sidecar).unwrap();
+        assert_eq!(extents.get(&tile), Some(extent));
+        fs::write(&tile, "49-00-00.0N 123-00-00.0W 10.0\n49-00-01.0N 123-00-00.0W 12.0\n").unwrap();
+        assert_eq!(extents.get(&tile), None);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn esri_ascii_extent_comes_from_the_header() {
+        let directory = std::env::temp_dir().join("thalweg-esri-ascii-extent");
+        fs::create_dir_all(&directory).unwrap();
+        let grid = directory.join("grid.asc");
+        fs::write(&grid, "ncols 3\nnrows 2\nxllcorner -123.0\nyllcorner 49.0\ncellsize 0.5\n1 2 3\n4 5 6\n").unwrap();
+        let extent = from_header(&grid).unwrap().unwrap();
+        assert_eq!(extent, Rect::new((-122.75, 49.25), (-121.75, 49.75)));
+        assert_eq!(from_header(&directory.join("tile.txt")).unwrap(), None);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn envelope_covers_every_point() {
+        let points = vec![
+            Bathymetry::new(49.1, -123.2, 1.0),
+            Bathymetry::new(49.3, -123.4, 1.0),
+            Bathymetry::new(49.2, -123.1, 1.0),
+        ];
+        let (west, south) = (points[1].point().0, points[0].point().1);
+        let (east, north) = (points[2].point().0, points[1].point().1);
+        assert_eq!(envelope(&points), Some(Rect::new((west, south), (east, north))));
+        assert_eq!(envelope(&[]), None);
+    }
+}
@@ -1,3 +1,199 @@
 pub mod bathymetry;
+pub mod extent;
 pub mod point;
+pub mod polygon;
 pub mod thalweg;
+pub mod tiles;
+
+use crate::bathymetry::{Bathymetry, Point};
+use crate::error::Error;
+
+use std::{fmt, str};
+
+use geo::algorithm::bounding_rect::BoundingRect;
+use geo::algorithm::contains::Contains;
+use geo::{MultiPolygon, Rect};
+
+/// How readers treat lines that cannot be parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Fail on the first malformed line
+    Strict,
+    /// Skip malformed lines, recording them alongside the data
+    Lenient,
+}
+
+/// Which way the values of a grid point, for grids with no metadata saying so, as the CF `positive` attribute
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Positive {
+    /// Values are depths below the surface
+    #[default]
+    Down,
+    /// Values are elevations, negative below the surface
+    Up,
+}
+
+impl Positive {
+    /// Positive-down depth of a grid value
+    pub(crate) fn depth(self, value: f64) -> f64 {
+        match self {
+            Positive::Down => value,
+            Positive::Up => -value,
+        }
+    }
+}
+
+impl str::FromStr for Positive {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "down" => Ok(Positive::Down),
+            "up" => Ok(Positive::Up),
+            _ => Err("expected up or down"),
+        }
+    }
+}
+
+impl fmt::Display for Positive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Positive::Down => write!(f, "down"),
+            Positive::Up => write!(f, "up"),
+        }
+    }
+}
+
+/// A line of input that was skipped while reading
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejected {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Values read from an input, along with any lines that were skipped
+#[derive(Debug, PartialEq)]
+pub struct Parsed<T> {
+    pub data: Vec<T>,
+    pub rejected: Vec<Rejected>,
+    /// Smallest rectangle around every value read, kept or not, for readers that go through every line
+    pub extent: Option<Rect<f64>>,
+}
+
+impl<T> Default for Parsed<T> {
+    fn default() -> Self {
+        Self {
+            data: vec![],
+            rejected: vec![],
+            extent: None,
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Parsed<T> {
+    fn from(data: Vec<T>) -> Self {
+        Self {
+            data,
+            rejected: vec![],
+            extent: None,
+        }
+    }
+}
+
+impl Parsed<Bathymetry> {
+    /// Keep a value if it is inside the bounds, widening the extent to cover it either way
+    pub(crate) fn keep_within(&mut self, value: Bathymetry, bounds: &Bounds) {
+        let (longitude, latitude) = value.point();
+        self.extent = Some(match self.extent {
+            Some(extent) => Rect::new(
+                (extent.min().x.min(longitude), extent.min().y.min(latitude)),
+                (extent.max().x.max(longitude), extent.max().y.max(latitude)),
+            ),
+            None => Rect::new((longitude, latitude), (longitude, latitude)),
+        });
+        if bounds.contains((longitude, latitude)) {
+            self.data.push(value);
+        }
+    }
+}
+
+impl ReadMode {
+    /// Record a line that failed to parse, or fail outright in strict mode
+    pub(crate) fn reject(self, rejected: &mut Vec<Rejected>, text: &str, error: Error) -> Result<(), Error> {
+        match (self, error) {
+            (
+                ReadMode::Lenient,
+                Error::Parse {
+                    line,
+                    column,
+                    message,
+                    ..
+                },
+            ) => {
+                rejected.push(Rejected {
+                    line,
+                    column,
+                    text: text.trim().to_string(),
+                    reason: message,
+                });
+                Ok(())
+            }
+            (_, error) => Err(error),
+        }
+    }
+}
+
+/// Area of interest and its envelope, which settles most points and tiles outside it with a few comparisons
+pub(crate) struct Bounds<'a> {
+    polygon: Option<(&'a MultiPolygon<f64>, Option<Rect<f64>>)>,
+}
+
+impl<'a> Bounds<'a> {
+    pub(crate) fn new(bb: &'a Option<MultiPolygon<f64>>) -> Self {
+        Self {
+            polygon: bb.as_ref().map(|polygon| (polygon, polygon.bounding_rect())),
+        }
+    }
+
+    /// Whether the point is inside the bounds, or there are none
+    pub(crate) fn contains(&self, (longitude, latitude): Point) -> bool {
+        match self.polygon {
+            None => true,
+            Some((polygon, Some(envelope))) => {
+                envelope_contains(&envelope, longitude, latitude)
+                    && polygon.contains(&geo::Point::new(longitude, latitude))
+            }
+            // an empty polygon contains nothing
+            Some((_, None)) => false,
+        }
+    }
+
+    /// Whether anything within `extent` could be inside the bounds, judged by envelope alone
+    pub(crate) fn overlaps(&self, extent: &Rect<f64>) -> bool {
+        match self.polygon {
+            None => true,
+            Some((_, Some(envelope))) => {
+                extent.min().x <= envelope.max().x
+                    && extent.max().x >= envelope.min().x
+                    && extent.min().y <= envelope.max().y
+                    && extent.max().y >= envelope.min().y
+            }
+            Some((_, None)) => false,
+        }
+    }
+}
+
+fn envelope_contains(envelope: &Rect<f64>, longitude: f64, latitude: f64) -> bool {
+    longitude >= envelope.min().x
+        && longitude <= envelope.max().x
+        && latitude >= envelope.min().y
+        && latitude <= envelope.max().y
+}
+
+/// Fetch a column from a split CSV row, reporting short rows as parse errors
+pub(crate) fn csv_field<'a>(row: &[&'a str], index: usize, line: usize) -> Result<&'a str, Error> {
+    row.get(index)
+        .copied()
+        .ok_or_else(|| Error::parse(line, index + 1, "Missing value"))
+}
@@ -1,34 +1,43 @@
 use crate::bathymetry::Point;
+use crate::error::Error;
 use crate::parse;
+use crate::read;
 
 use std::collections::HashMap;
-use std::error::Error;
 use std::io::{BufRead, BufReader, Read};
 
 /// Read point data from the NONNA-10 ASCII format
-pub fn from_nonna<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Box<dyn Error>> {
+pub fn from_nonna<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Error> {
     let mut out = vec![];
     let mut buffer = String::new();
+    let mut line = 0;
     loop {
         buffer.clear();
         if input.read_line(&mut buffer)? == 0 {
             break Ok(out);
         }
-        if let Some(value) = nonna_line(buffer.trim()) {
+        line += 1;
+        if let Ok(value) = nonna_line(buffer.trim(), line) {
             out.push(value);
         }
     }
 }
 
-fn nonna_line(input: &str) -> Option<Point> {
+fn nonna_line(input: &str, line: usize) -> Result<Point, Error> {
     let mut split = input.split(' ');
-    let latitude = parse::parse_dms_latitude(split.next()?)?;
-    let longitude = parse::parse_dms_longitude(split.next()?)?;
-    Some((longitude, latitude))
+    let latitude = split
+        .next()
+        .and_then(parse::parse_dms_latitude)
+        .ok_or_else(|| Error::parse(line, 1, "Invalid latitude"))?;
+    let longitude = split
+        .next()
+        .and_then(parse::parse_dms_longitude)
+        .ok_or_else(|| Error::parse(line, 2, "Invalid longitude"))?;
+    Ok((longitude, latitude))
 }
 
 /// Read point data from a CSV
-pub fn from_csv<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Box<dyn Error>> {
+pub fn from_csv<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Error> {
     let mut out = vec![];
     let mut buffer = String::new();
     // read header
@@ -43,27 +52,32 @@ pub fn from_csv<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Box<dyn
         .keys()
         .find(|name| name.to_lowercase().starts_with("la"))
         .and_then(|key| column_map.get(key))
-        .ok_or("Latitude not found")?;
+        .ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
     let longitude_index = *column_map
         .keys()
         .find(|name| name.to_lowercase().starts_with("lo"))
         .and_then(|key| column_map.get(key))
-        .ok_or("Longitude not found")?;
+        .ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
+    let mut line = 1;
     loop {
         buffer.clear();
         if input.read_line(&mut buffer)? == 0 {
             break Ok(out);
         }
+        line += 1;
+        if buffer.trim().is_empty() {
+            continue;
+        }
         let row: Vec<&str> = buffer.trim().split(',').collect();
-        let latitude = parse::parse_float(row[latitude_index]);
-        let longitude = parse::parse_float(row[longitude_index]);
+        let latitude = parse::parse_float(read::csv_field(&row, latitude_index, line)?);
+        let longitude = parse::parse_float(read::csv_field(&row, longitude_index, line)?);
         if let Some(point) = longitude.zip(latitude) {
             out.push(point);
         }
     }
 }
 
-pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Box<dyn Error>> {
+pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Error> {
     let mut buffer = String::new();
     input.read_to_string(&mut buffer)?;
     let geojson = json::parse(&buffer)?;
@@ -76,36 +90,37 @@ pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Box
     }
 }
 
-fn from_feature_collection(input: &json::JsonValue) -> Result<Vec<Point>, Box<dyn Error>> {
+fn from_feature_collection(input: &json::JsonValue) -> Result<Vec<Point>, Error> {
     let coll: Vec<&json::JsonValue> = input["features"].members().collect();
     from_features(&coll)
 }
 
-fn from_features(input: &[&json::JsonValue]) -> Result<Vec<Point>, Box<dyn Error>> {
+fn from_features(input: &[&json::JsonValue]) -> Result<Vec<Point>, Error> {
     let coll: Vec<&json::JsonValue> = input.iter().map(|item| &item["geometry"]).collect();
     from_points(&coll)
 }
 
-fn from_points(input: &[&json::JsonValue]) -> Result<Vec<Point>, Box<dyn Error>> {
+fn from_points(input: &[&json::JsonValue]) -> Result<Vec<Point>, Error> {
     let mut out = vec![];
-    if input.iter().any(|item| item["type"] != "Point") {
-        return Err(Box::<dyn Error>::from(
-            "Points can only be constructed from Point objects"
-        ));
+    if let Some(item) = input.iter().find(|item| item["type"] != "Point") {
+        return Err(Error::WrongGeometry {
+            expected: "Point",
+            found: item["type"].to_string(),
+        });
     }
     for point in input {
         let coords = &point["coordinates"];
         if coords.is_null() {
             continue;
         }
-        let longitude = coords[0].as_f64().ok_or("Missing longitude")?;
-        let latitude = coords[1].as_f64().ok_or("Missing latitude")?;
+        let longitude = coords[0].as_f64().ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
+        let latitude = coords[1].as_f64().ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
         out.push((longitude, latitude));
     }
     Ok(out)
 }
 
-pub fn from_geojson_line<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Box<dyn Error>> {
+pub fn from_geojson_line<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>, Error> {
     let mut buffer = String::new();
     input.read_to_string(&mut buffer)?;
     let geojson = json::parse(&buffer)?;
@@ -118,28 +133,29 @@ pub fn from_geojson_line<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Point>
     }
 }
 
-fn line_from_feature_collection(input: &json::JsonValue) -> Result<Vec<Point>, Box<dyn Error>> {
+fn line_from_feature_collection(input: &json::JsonValue) -> Result<Vec<Point>, Error> {
     line_from_features(&input["features"][0])
 }
 
-fn line_from_features(input: &json::JsonValue) -> Result<Vec<Point>, Box<dyn Error>> {
+fn line_from_features(input: &json::JsonValue) -> Result<Vec<Point>, Error> {
     points_from_line(&input["geometry"])
 }
 
-fn points_from_line(input: &json::JsonValue) -> Result<Vec<Point>, Box<dyn Error>> {
+fn points_from_line(input: &json::JsonValue) -> Result<Vec<Point>, Error> {
     let mut out = vec![];
     if input["type"] != "LineString" {
-        return Err(Box::<dyn Error>::from(
-            "Thalweg can only be constructed from a LineString object",
-        ));
+        return Err(Error::WrongGeometry {
+            expected: "LineString",
+            found: input["type"].to_string(),
+        });
     }
     let coordinates = &input["coordinates"];
     if coordinates.is_null() {
-        return Err(Box::<dyn Error>::from("No coordinates found"));
+        return Err(Error::MissingColumn("coordinates".to_string()));
     }
     for member in coordinates.members() {
-        let longitude = member[0].as_f64().ok_or("Missing longitude")?;
-        let latitude = member[1].as_f64().ok_or("Missing latitude")?;
+        let longitude = member[0].as_f64().ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
+        let latitude = member[1].as_f64().ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
         out.push((longitude, latitude));
     }
     Ok(out)
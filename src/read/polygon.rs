@@ -0,0 +1,459 @@
+use crate::bathymetry::Point;
+use crate::error::Error;
+
+use std::io::{BufReader, Read};
+
+use geo::algorithm::contains::Contains;
+use geo::{LineString, MultiPolygon, Polygon};
+
+/// Read Polygon and MultiPolygon geometries, bare or within Features and FeatureCollections
+pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<MultiPolygon<f64>, Error> {
+    let mut buffer = String::new();
+    input.read_to_string(&mut buffer)?;
+    let geojson = json::parse(&buffer)?;
+    let geometries: Vec<&json::JsonValue> = if geojson["type"] == "FeatureCollection" {
+        geojson["features"].members().map(|item| &item["geometry"]).collect()
+    } else if geojson["type"] == "Feature" {
+        vec![&geojson["geometry"]]
+    } else {
+        vec![&geojson]
+    };
+    let mut out = vec![];
+    for geometry in geometries {
+        if geometry["type"] == "Polygon" {
+            out.push(json_polygon(&geometry["coordinates"])?);
+        } else if geometry["type"] == "MultiPolygon" {
+            for polygon in geometry["coordinates"].members() {
+                out.push(json_polygon(polygon)?);
+            }
+        } else {
+            return Err(Error::WrongGeometry {
+                expected: "Polygon",
+                found: geometry["type"].to_string(),
+            });
+        }
+    }
+    Ok(MultiPolygon(out))
+}
+
+fn json_polygon(input: &json::JsonValue) -> Result<Polygon<f64>, Error> {
+    let mut rings = input.members().map(json_ring);
+    let exterior = rings
+        .next()
+        .unwrap_or_else(|| Err(Error::MissingColumn("coordinates".to_string())))?;
+    let interiors = rings.collect::<Result<Vec<_>, _>>()?;
+    Ok(Polygon::new(exterior, interiors))
+}
+
+fn json_ring(input: &json::JsonValue) -> Result<LineString<f64>, Error> {
+    let mut out = vec![];
+    for coords in input.members() {
+        let longitude = coords[0].as_f64().ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
+        let latitude = coords[1].as_f64().ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
+        out.push((longitude, latitude));
+    }
+    Ok(LineString::from(out))
+}
+
+/// Read POLYGON and MULTIPOLYGON geometries from well-known text, one after another
+///
+/// Z and M values are ignored, as is an EWKT `SRID=...;` prefix.
+pub fn from_wkt<T: Read>(input: &mut BufReader<T>) -> Result<MultiPolygon<f64>, Error> {
+    let mut buffer = String::new();
+    input.read_to_string(&mut buffer)?;
+    let mut text = Wkt { text: &buffer, at: 0 };
+    let mut out = vec![];
+    while !text.finished() {
+        let tag = text.word()?.to_uppercase();
+        if tag.starts_with("SRID=") {
+            text.expect(';')?;
+            continue;
+        }
+        if matches!(text.peek_word().as_str(), "Z" | "M" | "ZM") {
+            text.word()?;
+        }
+        match tag.as_str() {
+            "POLYGON" => out.push(text.polygon()?),
+            "MULTIPOLYGON" => out.extend(text.list(Wkt::polygon)?),
+            _ => {
+                return Err(Error::WrongGeometry {
+                    expected: "Polygon",
+                    found: tag,
+                })
+            }
+        }
+    }
+    Ok(MultiPolygon(out))
+}
+
+/// Cursor over well-known text
+struct Wkt<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl<'a> Wkt<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.at..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.at += rest.len() - rest.trim_start().len();
+    }
+
+    fn finished(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    fn error(&self, expected: &str) -> Error {
+        let before = &self.text[..self.at];
+        let line = before.matches('\n').count() + 1;
+        let column = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count() + 1;
+        Error::parse(line, column, format!("Malformed WKT: expected {}", expected))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.at += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", c)))
+        }
+    }
+
+    fn token(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | ',' | ';'))
+            .unwrap_or(rest.len());
+        self.at += end;
+        &rest[..end]
+    }
+
+    fn word(&mut self) -> Result<&'a str, Error> {
+        match self.token() {
+            "" => Err(self.error("a geometry type")),
+            word => Ok(word),
+        }
+    }
+
+    fn peek_word(&mut self) -> String {
+        let at = self.at;
+        let word = self.token().to_uppercase();
+        self.at = at;
+        word
+    }
+
+    /// Parenthesised, comma separated list of items
+    fn list<V>(&mut self, item: fn(&mut Self) -> Result<V, Error>) -> Result<Vec<V>, Error> {
+        self.expect('(')?;
+        let mut out = vec![item(self)?];
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with(',') {
+                self.at += 1;
+                out.push(item(self)?);
+            } else {
+                self.expect(')')?;
+                return Ok(out);
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, Error> {
+        self.skip_whitespace();
+        let at = self.at;
+        self.token().parse().map_err(|_| {
+            self.at = at;
+            self.error("a number")
+        })
+    }
+
+    fn coordinate(&mut self) -> Result<Point, Error> {
+        let position = (self.number()?, self.number()?);
+        // any Z and M values
+        loop {
+            self.skip_whitespace();
+            if self.rest().is_empty() || self.rest().starts_with([',', ')']) {
+                return Ok(position);
+            }
+            self.number()?;
+        }
+    }
+
+    fn ring(&mut self) -> Result<LineString<f64>, Error> {
+        self.list(Self::coordinate).map(LineString::from)
+    }
+
+    fn polygon(&mut self) -> Result<Polygon<f64>, Error> {
+        let mut rings = self.list(Self::ring)?.into_iter();
+        let exterior = rings.next().expect("list is never empty");
+        Ok(Polygon::new(exterior, rings.collect()))
+    }
+}
+
+/// Read Polygon, PolygonZ and PolygonM records from the main (.shp) file of an ESRI shapefile
+pub fn from_shapefile<T: Read>(input: &mut BufReader<T>) -> Result<MultiPolygon<f64>, Error> {
+    let mut buffer = vec![];
+    input.read_to_end(&mut buffer)?;
+    if buffer.len() < 100 || be_i32(&buffer, 0) != 9994 {
+        return Err(Error::InvalidInput("Not an ESRI shapefile".to_string()));
+    }
+
+    let mut rings = vec![];
+    let mut offset = 100;
+    while offset + 8 <= buffer.len() {
+        // record lengths are counted in 16-bit words
+        let start = offset + 8;
+        let end = usize::try_from(be_i32(&buffer, offset + 4))
+            .ok()
+            .and_then(|words| words.checked_mul(2))
+            .filter(|&length| length >= 4)
+            .and_then(|length| start.checked_add(length))
+            .filter(|&end| end <= buffer.len())
+            .ok_or_else(|| Error::InvalidInput("Truncated shapefile record".to_string()))?;
+        let record = &buffer[start..end];
+        match le_i32(record, 0) {
+            // null shape
+            0 => {}
+            5 | 15 | 25 => rings.extend(shape_rings(record)?),
+            other => {
+                return Err(Error::WrongGeometry {
+                    expected: "Polygon",
+                    found: format!("shape type {}", other),
+                })
+            }
+        }
+        offset = end;
+    }
+    Ok(assemble(rings))
+}
+
+fn shape_rings(record: &[u8]) -> Result<Vec<Vec<Point>>, Error> {
+    let truncated = || Error::InvalidInput("Truncated shapefile record".to_string());
+    // shape type and bounding box come before the part and point counts
+    if record.len() < 44 {
+        return Err(truncated());
+    }
+    // counts are signed in the file, and a negative one cannot be trusted any more than one that overruns the record
+    let num_parts = usize::try_from(le_i32(record, 36)).map_err(|_| truncated())?;
+    let num_points = usize::try_from(le_i32(record, 40)).map_err(|_| truncated())?;
+    let points_start = num_parts.checked_mul(4).and_then(|size| size.checked_add(44)).ok_or_else(truncated)?;
+    let points_end = num_points
+        .checked_mul(16)
+        .and_then(|size| size.checked_add(points_start))
+        .ok_or_else(truncated)?;
+    if record.len() < points_end {
+        return Err(truncated());
+    }
+    let mut starts = (0..num_parts)
+        .map(|i| usize::try_from(le_i32(record, 44 + 4 * i)).map_err(|_| truncated()))
+        .collect::<Result<Vec<usize>, Error>>()?;
+    starts.push(num_points);
+    let points: Vec<Point> = (0..num_points)
+        .map(|i| {
+            let at = points_start + 16 * i;
+            (le_f64(record, at), le_f64(record, at + 8))
+        })
+        .collect();
+    starts
+        .windows(2)
+        .map(|part| points.get(part[0]..part[1]).map(<[Point]>::to_vec).ok_or_else(truncated))
+        .collect()
+}
+
+/// Shapefiles list clockwise outer rings each followed by their anticlockwise holes
+fn assemble(rings: Vec<Vec<Point>>) -> MultiPolygon<f64> {
+    let (exteriors, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|ring| signed_area(ring) <= 0.0);
+    let mut polygons: Vec<Polygon<f64>> = exteriors
+        .into_iter()
+        .map(|ring| Polygon::new(LineString::from(ring), vec![]))
+        .collect();
+    for hole in holes {
+        let owner = hole
+            .first()
+            .and_then(|&first| polygons.iter().position(|p| p.contains(&geo::Point::from(first))));
+        match owner {
+            Some(index) => polygons[index].interiors_push(hole),
+            // a hole outside every ring is most likely an outer ring with the wrong winding
+            None => polygons.push(Polygon::new(LineString::from(hole), vec![])),
+        }
+    }
+    MultiPolygon(polygons)
+}
+
+/// Shoelace area, positive for anticlockwise rings
+fn signed_area(ring: &[Point]) -> f64 {
+    ring.windows(2).map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1).sum::<f64>() / 2.0
+}
+
+fn be_i32(buffer: &[u8], at: usize) -> i32 {
+    i32::from_be_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+}
+
+fn le_i32(buffer: &[u8], at: usize) -> i32 {
+    i32::from_le_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+}
+
+fn le_f64(buffer: &[u8], at: usize) -> f64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buffer[at..at + 8]);
+    f64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapefile(parts: &[&[Point]]) -> Vec<u8> {
+        let num_points: usize = parts.iter().map(|part| part.len()).sum();
+        let mut record = vec![];
+        record.extend(5i32.to_le_bytes());
+        record.extend([0u8; 32]);
+        record.extend((parts.len() as i32).to_le_bytes());
+        record.extend((num_points as i32).to_le_bytes());
+        let mut start = 0;
+        for part in parts {
+            record.extend((start as i32).to_le_bytes());
+            start += part.len();
+        }
+        for &(x, y) in parts.iter().flat_map(|part| part.iter()) {
+            record.extend(x.to_le_bytes());
+            record.extend(y.to_le_bytes());
+        }
+
+        let mut out = vec![];
+        out.extend(9994i32.to_be_bytes());
+        out.extend([0u8; 20]);
+        out.extend((((100 + 8 + record.len()) / 2) as i32).to_be_bytes());
+        out.extend(1000i32.to_le_bytes());
+        out.extend(5i32.to_le_bytes());
+        out.extend([0u8; 64]);
+        out.extend(1i32.to_be_bytes());
+        out.extend(((record.len() / 2) as i32).to_be_bytes());
+        out.extend(record);
+        out
+    }
+
+    #[test]
+    fn geojson_polygon_with_hole() {
+        let input = concat!(
+            "{\"type\":\"Polygon\",\"coordinates\":[",
+            "[[0,0],[4,0],[4,4],[0,4],[0,0]],",
+            "[[1,1],[1,2],[2,2],[2,1],[1,1]]",
+            "]}"
+        );
+        let land = from_geojson(&mut BufReader::new(input.as_bytes())).unwrap();
+        assert_eq!(land.0.len(), 1);
+        assert_eq!(land.0[0].interiors().len(), 1);
+    }
+
+    #[test]
+    fn geojson_feature_collection_of_multipolygons() {
+        let input = concat!(
+            "{\"type\":\"FeatureCollection\",\"features\":[",
+            "{\"type\":\"Feature\",\"geometry\":{\"type\":\"MultiPolygon\",\"coordinates\":[",
+            "[[[0,0],[1,0],[1,1],[0,0]]],",
+            "[[[2,2],[3,2],[3,3],[2,2]]]",
+            "]}},",
+            "{\"type\":\"Feature\",\"geometry\":{\"type\":\"Polygon\",\"coordinates\":[",
+            "[[5,5],[6,5],[6,6],[5,5]]",
+            "]}}",
+            "]}"
+        );
+        let land = from_geojson(&mut BufReader::new(input.as_bytes())).unwrap();
+        assert_eq!(land.0.len(), 3);
+    }
+
+    #[test]
+    fn geojson_rejects_other_geometry() {
+        let input = "{\"type\":\"LineString\",\"coordinates\":[[0,0],[1,1]]}";
+        let result = from_geojson(&mut BufReader::new(input.as_bytes()));
+        assert!(matches!(result, Err(Error::WrongGeometry { expected: "Polygon", .. })));
+    }
+
+    #[test]
+    fn wkt_polygon_with_hole() {
+        let input = "POLYGON ((0 0, 4 0, 4 4, 0 4, 0 0), (1 1, 1 2, 2 2, 2 1, 1 1))";
+        let area = from_wkt(&mut BufReader::new(input.as_bytes())).unwrap();
+        assert_eq!(area.0.len(), 1);
+        assert_eq!(area.0[0].exterior().0.len(), 5);
+        assert_eq!(area.0[0].interiors().len(), 1);
+        assert!(area.contains(&geo::Point::new(3.0, 3.0)));
+        assert!(!area.contains(&geo::Point::new(1.5, 1.5)));
+    }
+
+    #[test]
+    fn wkt_multipolygon_with_z_values_and_srid() {
+        let input = concat!(
+            "SRID=4326;MULTIPOLYGON Z (((-123.5 49.0 0, -123.0 49.0 0, -123.0 49.5 0, -123.5 49.0 0)),\n",
+            "  ((-124 50 1, -123.5 50 1, -123.5 50.5 1, -124 50 1)))\n",
+            "polygon((5 5,6 5,6 6,5 5))"
+        );
+        let area = from_wkt(&mut BufReader::new(input.as_bytes())).unwrap();
+        assert_eq!(area.0.len(), 3);
+        assert_eq!(area.0[1].exterior().0[1].x, -123.5);
+        assert_eq!(area.0[1].exterior().0[1].y, 50.0);
+    }
+
+    #[test]
+    fn wkt_rejects_other_geometry_and_malformed_text() {
+        let result = from_wkt(&mut BufReader::new("LINESTRING (0 0, 1 1)".as_bytes()));
+        assert!(matches!(result, Err(Error::WrongGeometry { expected: "Polygon", .. })));
+        let result = from_wkt(&mut BufReader::new("POLYGON ((0 0, 1 x, 0 0))".as_bytes()));
+        assert!(matches!(result, Err(Error::Parse { line: 1, column: 18, .. })));
+        let result = from_wkt(&mut BufReader::new("POLYGON (\n  (0 0, 1 1, 0 0)".as_bytes()));
+        assert!(matches!(result, Err(Error::Parse { line: 2, column: 18, .. })));
+    }
+
+    #[test]
+    fn shapefile_polygon_with_hole() {
+        let exterior = [(0.0, 0.0), (0.0, 4.0), (4.0, 4.0), (4.0, 0.0), (0.0, 0.0)];
+        let hole = [(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 2.0), (1.0, 1.0)];
+        let input = shapefile(&[&exterior, &hole]);
+        let land = from_shapefile(&mut BufReader::new(input.as_slice())).unwrap();
+        assert_eq!(land.0.len(), 1);
+        assert_eq!(land.0[0].exterior().0.len(), 5);
+        assert_eq!(land.0[0].interiors().len(), 1);
+    }
+
+    #[test]
+    fn shapefile_separate_islands() {
+        let first = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.0, 0.0)];
+        let second = [(5.0, 5.0), (5.0, 6.0), (6.0, 6.0), (5.0, 5.0)];
+        let input = shapefile(&[&first, &second]);
+        let land = from_shapefile(&mut BufReader::new(input.as_slice())).unwrap();
+        assert_eq!(land.0.len(), 2);
+    }
+
+    #[test]
+    fn shapefile_rejects_negative_counts() {
+        let square: &[Point] = &[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)];
+        // number of parts, in the record after its 8 byte header
+        let mut input = shapefile(&[square]);
+        input[100 + 8 + 36..100 + 8 + 40].copy_from_slice(&(-1i32).to_le_bytes());
+        let result = from_shapefile(&mut BufReader::new(&input[..]));
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+        // record length, in the record header
+        let mut input = shapefile(&[square]);
+        input[100 + 4..100 + 8].copy_from_slice(&(-1i32).to_be_bytes());
+        let result = from_shapefile(&mut BufReader::new(&input[..]));
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+        // start of a part
+        let mut input = shapefile(&[square]);
+        input[100 + 8 + 44..100 + 8 + 48].copy_from_slice(&(-4i32).to_le_bytes());
+        let result = from_shapefile(&mut BufReader::new(&input[..]));
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn shapefile_rejects_other_files() {
+        let input = [0u8; 100];
+        let result = from_shapefile(&mut BufReader::new(&input[..]));
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+}
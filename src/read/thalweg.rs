@@ -1,23 +1,23 @@
 use crate::bathymetry::Bathymetry;
+use crate::error::Error;
 use crate::read::bathymetry;
 
-use std::error::Error;
 use std::io::{BufReader, Read};
 
 use json;
 
 /// Read thalweg data from the NONNA-10 ASCII format
-pub fn from_nonna<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
+pub fn from_nonna<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>, Error> {
     bathymetry::from_nonna(input, &None)
 }
 
 /// Read thalweg data from a CSV
-pub fn from_csv<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
+pub fn from_csv<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>, Error> {
     bathymetry::from_csv(input, &None)
 }
 
 /// Read thalweg data from GeoJSON
-pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
+pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>, Error> {
     let mut buffer = String::new();
     input.read_to_string(&mut buffer)?;
     let geojson = json::parse(&buffer)?;
@@ -30,29 +30,30 @@ pub fn from_geojson<T: Read>(input: &mut BufReader<T>) -> Result<Vec<Bathymetry>
     }
 }
 
-fn from_feature_collection(input: &json::JsonValue) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
+fn from_feature_collection(input: &json::JsonValue) -> Result<Vec<Bathymetry>, Error> {
     from_feature(&input["features"][0])
 }
 
-fn from_feature(input: &json::JsonValue) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
+fn from_feature(input: &json::JsonValue) -> Result<Vec<Bathymetry>, Error> {
     from_line_string(&input["geometry"])
 }
 
-fn from_line_string(input: &json::JsonValue) -> Result<Vec<Bathymetry>, Box<dyn Error>> {
+fn from_line_string(input: &json::JsonValue) -> Result<Vec<Bathymetry>, Error> {
     let mut out = vec![];
     if input["type"] != "LineString" {
-        return Err(Box::<dyn Error>::from(
-            "Thalweg can only be constructed from a LineString object",
-        ));
+        return Err(Error::WrongGeometry {
+            expected: "LineString",
+            found: input["type"].to_string(),
+        });
     }
     let coordinates = &input["coordinates"];
     if coordinates.is_null() {
-        return Err(Box::<dyn Error>::from("No coordinates found"));
+        return Err(Error::MissingColumn("coordinates".to_string()));
     }
     for member in coordinates.members() {
-        let longitude = member[0].as_f64().ok_or("Missing longitude")?;
-        let latitude = member[1].as_f64().ok_or("Missing latitude")?;
-        let elevation = member[2].as_f64().ok_or("Missing elevation")?;
+        let longitude = member[0].as_f64().ok_or_else(|| Error::MissingColumn("Longitude".to_string()))?;
+        let latitude = member[1].as_f64().ok_or_else(|| Error::MissingColumn("Latitude".to_string()))?;
+        let elevation = member[2].as_f64().ok_or_else(|| Error::MissingColumn("Elevation".to_string()))?;
         out.push(Bathymetry::new(latitude, longitude, -1.0 * elevation));
     }
     Ok(out)
@@ -0,0 +1,294 @@
+use crate::bathymetry::Bathymetry;
+use crate::cache;
+use crate::error::Error;
+use crate::read::extent::{self, Extents};
+use crate::read::{bathymetry, Bounds, Parsed, Positive, ReadMode};
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use geo::{MultiPolygon, Rect};
+
+/// Bathymetry read from one file of a data directory
+#[derive(Debug)]
+pub struct Tile {
+    pub path: PathBuf,
+    pub parsed: Parsed<Bathymetry>,
+    /// Extent of every point in the file, when it was read line by line and it was not already known
+    pub extent: Option<Rect<f64>>,
+    /// Whether the file was passed over for lying outside the bounding box
+    pub skipped: bool,
+}
+
+/// How far loading has got, reported after each file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub files: usize,
+    pub total: usize,
+    pub skipped: usize,
+    pub points: usize,
+}
+
+/// Files in `directory` with an extension, other than hidden files and caches,
+/// sorted so that every filesystem gives the same order
+pub fn list<P: AsRef<Path>>(directory: P) -> Result<Vec<PathBuf>, Error> {
+    let mut out = vec![];
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        if path.extension().is_some() && !hidden && !cache::is_cache_file(&path) {
+            out.push(path);
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+/// Read a bathymetry file, choosing the reader by its extension and falling back to NONNA-10.
+/// `positive` says which way the values of ESRI ASCII grids point, as nothing in the file does
+pub fn read_file(
+    path: &Path,
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+    positive: Positive,
+) -> Result<Parsed<Bathymetry>, Error> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    let values = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => bathymetry::from_csv_with_mode(&mut reader, bb, mode),
+        Some("asc") => bathymetry::from_esri_ascii(&mut reader, bb, positive).map(Parsed::from),
+        Some("xyz") => bathymetry::from_xyz_with_mode(&mut reader, bb, mode),
+        #[cfg(feature = "netcdf")]
+        Some("nc") => bathymetry::from_netcdf(path, bb).map(Parsed::from),
+        Some("tif") | Some("tiff") => bathymetry::from_geotiff(&mut reader, bb).map(Parsed::from),
+        _ => bathymetry::from_nonna_with_mode(&mut reader, bb, mode),
+    };
+    values.map_err(|e| e.with_file(path))
+}
+
+/// Read a file unless its extent, from `known` or its header, shows it lies outside the bounding box.
+/// Every line of files read line by line is checked against the box as it is read, and the extent of those lines
+/// is recorded when it was not already known, as nothing short of reading them all shows where they lie
+pub fn read_tile(
+    path: &Path,
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+    positive: Positive,
+    known: &Extents,
+) -> Result<Tile, Error> {
+    let bounds = Bounds::new(bb);
+    let recorded = known.get(path);
+    let extent = match (bb, recorded) {
+        (None, _) => None,
+        (Some(_), Some(extent)) => Some(extent),
+        (Some(_), None) => extent::from_header(path)?,
+    };
+    if extent.is_some_and(|extent| !bounds.overlaps(&extent)) {
+        return Ok(Tile {
+            path: path.to_path_buf(),
+            parsed: Parsed::default(),
+            extent: None,
+            skipped: true,
+        });
+    }
+    let parsed = read_file(path, bb, mode, positive)?;
+    Ok(Tile {
+        path: path.to_path_buf(),
+        extent: if recorded.is_none() { parsed.extent } else { None },
+        parsed,
+        skipped: false,
+    })
+}
+
+/// Read every file on up to `workers` threads, one file at a time per thread, skipping those outside the box.
+/// Tiles come back in the order of `paths` however the work was shared out, and `progress` is called on the
+/// calling thread as each file finishes. If any file fails, the error of the first such file in `paths` is returned
+pub fn read_all<F: FnMut(Progress)>(
+    paths: &[PathBuf],
+    bb: &Option<MultiPolygon<f64>>,
+    mode: ReadMode,
+    positive: Positive,
+    known: &Extents,
+    workers: usize,
+    mut progress: F,
+) -> Result<Vec<Tile>, Error> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let mut results: Vec<Option<Result<Tile, Error>>> = paths.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers.clamp(1, paths.len().max(1)) {
+            let sender = sender.clone();
+            let (next, failed) = (&next, &failed);
+            scope.spawn(move || {
+                // stop taking new files once one has failed, as the result will be discarded
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else { break };
+                    let result = read_tile(path, bb, mode, positive, known);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    if sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // the loop below ends once every worker has dropped its sender
+        drop(sender);
+
+        let mut status = Progress {
+            files: 0,
+            total: paths.len(),
+            skipped: 0,
+            points: 0,
+        };
+        for (index, result) in receiver {
+            status.files += 1;
+            if let Ok(tile) = &result {
+                status.skipped += tile.skipped as usize;
+                status.points += tile.parsed.data.len();
+            }
+            results[index] = Some(result);
+            progress(status);
+        }
+    });
+
+    // files are handed out in order and always finish, so any skipped come after the first failure
+    results
+        .into_iter()
+        .map(|result| result.expect("file skipped without a failure"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn tile_directory(name: &str, tiles: usize) -> PathBuf {
+        let directory = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for tile in 0..tiles {
+            let mut contents = String::from("latitude,longitude,depth\n");
+            for row in 0..=tile {
+                contents += &format!("{},{},{}\n", 49.0 + tile as f64 * 0.01, -123.0 + row as f64 * 0.01, tile + 1);
+            }
+            fs::write(directory.join(format!("tile{:02}.csv", tile)), contents).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn tiles_are_in_path_order_for_any_number_of_workers() {
+        let directory = tile_directory("thalweg-tiles-are-in-path-order", 12);
+        let paths = list(&directory).unwrap();
+        assert_eq!(paths.len(), 12);
+        let sequential = read_all(&paths, &None, ReadMode::Strict, Positive::Down, &Extents::default(), 1, |_| {}).unwrap();
+        for workers in [2, 5, 32] {
+            let parallel = read_all(&paths, &None, ReadMode::Strict, Positive::Down, &Extents::default(), workers, |_| {}).unwrap();
+            let order: Vec<&PathBuf> = parallel.iter().map(|tile| &tile.path).collect();
+            assert_eq!(order, paths.iter().collect::<Vec<_>>());
+            for (a, b) in sequential.iter().zip(&parallel) {
+                assert_eq!(a.parsed, b.parsed);
+            }
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn progress_counts_files_and_points() {
+        let directory = tile_directory("thalweg-progress-counts-files-and-points", 4);
+        let paths = list(&directory).unwrap();
+        let mut reports = vec![];
+        read_all(&paths, &None, ReadMode::Strict, Positive::Down, &Extents::default(), 3, |progress| reports.push(progress)).unwrap();
+        let files: Vec<usize> = reports.iter().map(|progress| progress.files).collect();
+        assert_eq!(files, vec![1, 2, 3, 4]);
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                files: 4,
+                total: 4,
+                skipped: 0,
+                points: 1 + 2 + 3 + 4
+            })
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn failing_file_is_reported() {
+        let directory = tile_directory("thalweg-failing-file-is-reported", 3);
+        fs::write(directory.join("tile01.csv"), "latitude,longitude,depth\n49.0,oops,1\n").unwrap();
+        let paths = list(&directory).unwrap();
+        let result = read_all(&paths, &None, ReadMode::Strict, Positive::Down, &Extents::default(), 2, |_| {});
+        match result {
+            Err(Error::Parse { file, .. }) => assert_eq!(file, Some(directory.join("tile01.csv"))),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn tiles_outside_the_box_are_skipped_once_their_extent_is_known() {
+        let directory = tile_directory("thalweg-tiles-outside-the-box-are-skipped", 10);
+        let paths = list(&directory).unwrap();
+        // tiles 3 to 7 lie between these latitudes
+        let bb = parse::parse_bounding_box("-123.1,49.025,-122.0,49.075").map(MultiPolygon::from);
+
+        let mut known = Extents::default();
+        let first = read_all(&paths, &bb, ReadMode::Strict, Positive::Down, &known, 4, |_| {}).unwrap();
+        assert!(first.iter().all(|tile| !tile.skipped && tile.extent.is_some()));
+        for tile in &first {
+            known.insert(tile.path.clone(), tile.extent.unwrap()).unwrap();
+        }
+
+        let mut last = None;
+        let second = read_all(&paths, &bb, ReadMode::Strict, Positive::Down, &known, 4, |progress| last = Some(progress)).unwrap();
+        let skipped: Vec<bool> = second.iter().map(|tile| tile.skipped).collect();
+        assert_eq!(skipped, vec![true, true, true, false, false, false, false, false, true, true]);
+        assert_eq!(last.map(|progress| progress.skipped), Some(5));
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.parsed.data, b.parsed.data);
+        }
+        assert_eq!(second.iter().map(|tile| tile.parsed.data.len()).sum::<usize>(), 4 + 5 + 6 + 7 + 8);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn nonna_tiles_are_read_until_their_extent_is_known() {
+        let directory = std::env::temp_dir().join("thalweg-nonna-tiles-are-read-until-their-extent-is-known");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        // out of order, so the first and last lines are both well north of the box the middle one is in
+        let contents = "50-00-00.00N 123-00-00.00W 10.0\n49-15-00.00N 123-00-00.00W 20.0\n50-30-00.00N 123-00-00.00W 30.0\n";
+        fs::write(directory.join("tile.txt"), contents).unwrap();
+        let paths = list(&directory).unwrap();
+        let bb = parse::parse_bounding_box("-123.5,49.1,-122.5,49.6").map(MultiPolygon::from);
+
+        let mut known = Extents::default();
+        let first = read_all(&paths, &bb, ReadMode::Strict, Positive::Down, &known, 1, |_| {}).unwrap();
+        assert!(!first[0].skipped);
+        assert_eq!(first[0].parsed.data, vec![Bathymetry::new(49.25, -123.0, 20.0)]);
+        assert_eq!(first[0].extent, Some(Rect::new((-123.0, 49.25), (-123.0, 50.5))));
+
+        // once the whole tile has been read, its recorded extent decides
+        known.insert(paths[0].clone(), first[0].extent.unwrap()).unwrap();
+        let second = read_all(&paths, &bb, ReadMode::Strict, Positive::Down, &known, 1, |_| {}).unwrap();
+        assert_eq!(second[0].parsed.data, first[0].parsed.data);
+        let elsewhere = parse::parse_bounding_box("-123.5,48.0,-122.5,49.0").map(MultiPolygon::from);
+        let third = read_all(&paths, &elsewhere, ReadMode::Strict, Positive::Down, &known, 1, |_| {}).unwrap();
+        assert!(third[0].skipped);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
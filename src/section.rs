@@ -1,28 +1,51 @@
-use crate::bathymetry::Bathymetry;
+use crate::bathymetry::{Bathymetry, Point};
 
 #[derive(Debug, PartialEq)]
 pub struct Section {
     distance: isize,
     depth: f64,
+    point: Point,
 }
 
 impl Section {
-    fn new(distance: isize, depth: f64) -> Self {
-        Self { distance, depth }
+    fn new(distance: isize, depth: f64, point: Point) -> Self {
+        Self {
+            distance,
+            depth,
+            point,
+        }
+    }
+
+    /// Distance from the start of the thalweg in metres
+    pub fn distance(&self) -> isize {
+        self.distance
+    }
+
+    pub fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    pub fn point(&self) -> Point {
+        self.point
     }
 }
 
 pub fn section(path: &[Bathymetry]) -> Vec<Section> {
-    let mut distance_from_start = 0;
+    section_from(path, 0)
+}
+
+/// Section of a path that begins `start` metres along a longer one, such as a branch of a network
+pub fn section_from(path: &[Bathymetry], start: isize) -> Vec<Section> {
+    let mut distance_from_start = start;
     let mut out = vec![];
     if let Some(start) = path.get(0) {
-        out.push(Section::new(distance_from_start, start.depth()));
+        out.push(Section::new(distance_from_start, start.depth(), start.point()));
     }
     for window in path.windows(2) {
         let start = window[0].clone();
         let end = window[1].clone();
         distance_from_start += start.distance_to(&end) as isize;
-        out.push(Section::new(distance_from_start, end.depth()));
+        out.push(Section::new(distance_from_start, end.depth(), end.point()));
     }
     out
 }
@@ -42,7 +65,7 @@ mod tests {
     #[test]
     fn section_from_single_point() {
         let input = vec![Bathymetry::new(0.0, 0.0, 0.0)];
-        assert_eq!(section(&input), vec![Section::new(0, 0.0)]);
+        assert_eq!(section(&input), vec![Section::new(0, 0.0, (0.0, 0.0))]);
     }
 
     #[test]
@@ -53,9 +76,9 @@ mod tests {
             Bathymetry::new(49.24, -122.46, 107.072),
         ];
         let expected = vec![
-            Section::new(0, 157.692),
-            Section::new(4355, 96.996),
-            Section::new(9436, 107.072),
+            Section::new(0, 157.692, (-122.59, 49.24)),
+            Section::new(4355, 96.996, (-122.53, 49.24)),
+            Section::new(9436, 107.072, (-122.46, 49.24)),
         ];
         assert_eq!(section(&input), expected);
     }
@@ -69,14 +92,17 @@ mod tests {
 
     #[test]
     fn section_to_csv_one_point() {
-        let input = vec![Section::new(0, 157.692)];
+        let input = vec![Section::new(0, 157.692, (-122.59, 49.24))];
         let expected = "distance,depth\n0,157.692\n";
         assert_eq!(to_csv(&input), expected);
     }
 
     #[test]
     fn section_to_csv_many_points() {
-        let input = vec![Section::new(0, 157.692), Section::new(4355, 96.996)];
+        let input = vec![
+            Section::new(0, 157.692, (-122.59, 49.24)),
+            Section::new(4355, 96.996, (-122.53, 49.24)),
+        ];
         let expected = "distance,depth\n0,157.692\n4.355,96.996\n";
         assert_eq!(to_csv(&input), expected);
     }
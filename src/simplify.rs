@@ -0,0 +1,146 @@
+use crate::bathymetry::Point;
+use crate::index::EARTH_RADIUS;
+
+use geo::algorithm::simplify::Simplify;
+use geo::algorithm::simplifyvw::SimplifyVWPreserve;
+use geo::LineString;
+
+use std::fmt;
+use std::str;
+
+// close to the fixed area of 1/18000 square degrees once used, which is about (650 m)² at 50°N
+const DEFAULT_VISVALINGAM: f64 = 650.0;
+
+const DEFAULT_DOUGLAS_PEUCKER: f64 = 10.0;
+
+/// Built-in simplification and its tolerance in metres, written as `name` or `name:tolerance`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Simplification {
+    /// Douglas-Peucker, keeping every point further than the tolerance from the simplified line
+    DouglasPeucker(f64),
+    /// Visvalingam-Whyatt, removing points whose triangle with their neighbours has an area less than the square
+    /// of the tolerance, without letting the line cross itself
+    Visvalingam(f64),
+}
+
+impl Default for Simplification {
+    fn default() -> Self {
+        Simplification::Visvalingam(DEFAULT_VISVALINGAM)
+    }
+}
+
+impl str::FromStr for Simplification {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let (name, tolerance) = match lower.split_once(':') {
+            Some((name, tolerance)) => {
+                let value = tolerance.trim().parse::<f64>().map_err(|_| "invalid simplification tolerance")?;
+                if !value.is_finite() || value <= 0.0 {
+                    return Err("simplification tolerance must be a positive number");
+                }
+                (name.trim(), Some(value))
+            }
+            None => (lower.trim(), None),
+        };
+        match name {
+            "douglas-peucker" => Ok(Simplification::DouglasPeucker(tolerance.unwrap_or(DEFAULT_DOUGLAS_PEUCKER))),
+            "visvalingam" => Ok(Simplification::Visvalingam(tolerance.unwrap_or(DEFAULT_VISVALINGAM))),
+            _ => Err("unrecognized simplification"),
+        }
+    }
+}
+
+impl fmt::Display for Simplification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Simplification::DouglasPeucker(tolerance) => write!(f, "douglas-peucker:{}", tolerance),
+            Simplification::Visvalingam(tolerance) => write!(f, "visvalingam:{}", tolerance),
+        }
+    }
+}
+
+/// Indices of the points left by simplifying the line through `points`, always including the first and last
+/// and those in `keep`
+///
+/// The points are first projected onto a plane in metres about their mean latitude, so that the tolerance means
+/// the same at any latitude, and the line is simplified piece by piece between the points that must stay.
+pub fn simplify(points: &[Point], simplification: Simplification, keep: &[usize]) -> Vec<usize> {
+    if points.len() < 3 {
+        return (0..points.len()).collect();
+    }
+    let latitude = points.iter().map(|&(_, latitude)| latitude).sum::<f64>() / points.len() as f64;
+    let scale = latitude.to_radians().cos();
+    let projected: Vec<Point> = points
+        .iter()
+        .map(|&(x, y)| (x.to_radians() * EARTH_RADIUS * scale, y.to_radians() * EARTH_RADIUS))
+        .collect();
+
+    let last = points.len() - 1;
+    let mut breaks: Vec<usize> = keep.iter().copied().filter(|&index| index <= last).chain([0, last]).collect();
+    breaks.sort_unstable();
+    breaks.dedup();
+
+    let mut out = vec![0];
+    for pair in breaks.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let piece: LineString<f64> = projected[start..=end].iter().copied().collect();
+        let simplified = match simplification {
+            Simplification::DouglasPeucker(tolerance) => piece.simplify(&tolerance),
+            Simplification::Visvalingam(tolerance) => piece.simplifyvw_preserve(&(tolerance * tolerance)),
+        };
+        // the simplified line is made of the original coordinates, in order, so each can be found again
+        let mut index = start;
+        for coordinate in simplified.coords().skip(1) {
+            index += 1;
+            while index < end && projected[index] != coordinate.x_y() {
+                index += 1;
+            }
+            out.push(index);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // line running east with points about ten metres apart, one bowed `offset` metres north, at the given latitude
+    fn bowed(latitude: f64, offset: f64) -> Vec<Point> {
+        let north = (offset / EARTH_RADIUS).to_degrees();
+        (0..5)
+            .map(|x| (x as f64 * 0.0001, if x == 2 { latitude + north } else { latitude }))
+            .collect()
+    }
+
+    #[test]
+    fn tolerance_is_the_same_at_any_latitude() {
+        for latitude in [0.0, 48.0, 60.0] {
+            for simplification in [Simplification::DouglasPeucker(10.0), Simplification::Visvalingam(10.0)] {
+                assert_eq!(simplify(&bowed(latitude, 3.0), simplification, &[]), vec![0, 4], "{}", simplification);
+            }
+            assert_eq!(simplify(&bowed(latitude, 20.0), Simplification::DouglasPeucker(10.0), &[]), vec![0, 2, 4]);
+        }
+    }
+
+    #[test]
+    fn points_to_keep_are_never_removed() {
+        let points = bowed(48.0, 5.0);
+        assert_eq!(simplify(&points, Simplification::DouglasPeucker(10.0), &[1]), vec![0, 1, 4]);
+        assert_eq!(simplify(&points, Simplification::Visvalingam(10.0), &[1, 3]), vec![0, 1, 3, 4]);
+        assert_eq!(simplify(&points[..2], Simplification::Visvalingam(10.0), &[]), vec![0, 1]);
+    }
+
+    #[test]
+    fn parse_simplification_names() {
+        assert_eq!("douglas-peucker".parse(), Ok(Simplification::DouglasPeucker(10.0)));
+        assert_eq!("Visvalingam:2.5".parse(), Ok(Simplification::Visvalingam(2.5)));
+        assert_eq!("visvalingam".parse(), Ok(Simplification::Visvalingam(650.0)));
+        assert_eq!(Simplification::default().to_string().parse(), Ok(Simplification::default()));
+        assert!("visvalingam:-1".parse::<Simplification>().is_err());
+        assert!("douglas-peucker:nan".parse::<Simplification>().is_err());
+        assert!("visvalingam:inf".parse::<Simplification>().is_err());
+        assert!("radial".parse::<Simplification>().is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::bathymetry::Point;
+
+use geo::algorithm::haversine_distance::HaversineDistance;
+
+use std::fmt;
+use std::str;
+
+// each round of Chaikin corner cutting doubles the number of points
+const MAX_CHAIKIN_ITERATIONS: usize = 10;
+
+// spline samples multiply the number of points alike, so they are held to the same growth
+const MAX_SPLINE_SAMPLES: usize = 1 << MAX_CHAIKIN_ITERATIONS;
+
+/// Built-in smoothing and its parameter, written as `name` or `name:parameter`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Smoothing {
+    /// Chaikin corner cutting, repeated this many times
+    Chaikin(usize),
+    /// Catmull-Rom spline through every point, sampled this many times between each pair
+    CatmullRom(usize),
+    /// Uniform cubic B-spline drawn towards every point, sampled this many times between each pair
+    BSpline(usize),
+    /// Moving average of the points within a window of this many metres along the path
+    Window(f64),
+}
+
+impl str::FromStr for Smoothing {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let (name, parameter) = match lower.split_once(':') {
+            Some((name, parameter)) => {
+                let value = parameter.trim().parse::<f64>().map_err(|_| "invalid smoothing parameter")?;
+                if !value.is_finite() || value <= 0.0 {
+                    return Err("smoothing parameter must be a positive number");
+                }
+                (name.trim(), Some(value))
+            }
+            None => (lower.trim(), None),
+        };
+        let count = |default: usize| match parameter {
+            Some(value) if value.fract() != 0.0 => Err("smoothing parameter must be a whole number"),
+            Some(value) => Ok(value as usize),
+            None => Ok(default),
+        };
+        match name {
+            "chaikin" => match count(3)? {
+                iterations if iterations > MAX_CHAIKIN_ITERATIONS => Err("too many chaikin iterations"),
+                iterations => Ok(Smoothing::Chaikin(iterations)),
+            },
+            "catmull-rom" => match count(4)? {
+                samples if samples > MAX_SPLINE_SAMPLES => Err("too many catmull-rom samples"),
+                samples => Ok(Smoothing::CatmullRom(samples)),
+            },
+            "bspline" => match count(4)? {
+                samples if samples > MAX_SPLINE_SAMPLES => Err("too many bspline samples"),
+                samples => Ok(Smoothing::BSpline(samples)),
+            },
+            "window" => Ok(Smoothing::Window(parameter.unwrap_or(100.0))),
+            _ => Err("unrecognized smoothing"),
+        }
+    }
+}
+
+impl fmt::Display for Smoothing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Smoothing::Chaikin(iterations) => write!(f, "chaikin:{}", iterations),
+            Smoothing::CatmullRom(samples) => write!(f, "catmull-rom:{}", samples),
+            Smoothing::BSpline(samples) => write!(f, "bspline:{}", samples),
+            Smoothing::Window(length) => write!(f, "window:{}", length),
+        }
+    }
+}
+
+/// Smoothed line through `points`, keeping the first and last, with each new point's position along the
+/// original as a fractional index into `points`
+pub fn smooth(points: &[Point], smoothing: Smoothing) -> Vec<(Point, f64)> {
+    let indexed = points.iter().enumerate().map(|(index, &point)| (point, index as f64)).collect();
+    if points.len() < 3 {
+        return indexed;
+    }
+    match smoothing {
+        Smoothing::Chaikin(iterations) => (0..iterations).fold(indexed, |line, _| chaikin(&line)),
+        Smoothing::CatmullRom(samples) => spline(points, samples, catmull_rom),
+        Smoothing::BSpline(samples) => spline(points, samples, b_spline),
+        Smoothing::Window(length) => window(points, length),
+    }
+}
+
+/// Weighted sum of points and their positions
+fn mix(weights: &[f64], points: &[(Point, f64)]) -> (Point, f64) {
+    let mut out = ((0.0, 0.0), 0.0);
+    for (weight, &((x, y), position)) in weights.iter().zip(points) {
+        out.0 .0 += weight * x;
+        out.0 .1 += weight * y;
+        out.1 += weight * position;
+    }
+    out
+}
+
+/// One round of cutting every corner a quarter of the way along each segment, leaving the ends in place
+fn chaikin(line: &[(Point, f64)]) -> Vec<(Point, f64)> {
+    let mut out = vec![line[0]];
+    for pair in line.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        out.push(mix(&[0.75, 0.25], &[a, b]));
+        out.push(mix(&[0.25, 0.75], &[a, b]));
+    }
+    out.push(line[line.len() - 1]);
+    out
+}
+
+/// Catmull-Rom weights of four control points at `u` between the middle two
+fn catmull_rom(u: f64) -> [f64; 4] {
+    let (u2, u3) = (u * u, u * u * u);
+    [
+        0.5 * (-u3 + 2.0 * u2 - u),
+        0.5 * (3.0 * u3 - 5.0 * u2 + 2.0),
+        0.5 * (-3.0 * u3 + 4.0 * u2 + u),
+        0.5 * (u3 - u2),
+    ]
+}
+
+/// Uniform cubic B-spline weights of four control points at `u` between the middle two
+fn b_spline(u: f64) -> [f64; 4] {
+    let (u2, u3) = (u * u, u * u * u);
+    [
+        (1.0 - u).powi(3) / 6.0,
+        (3.0 * u3 - 6.0 * u2 + 4.0) / 6.0,
+        (-3.0 * u3 + 3.0 * u2 + 3.0 * u + 1.0) / 6.0,
+        u3 / 6.0,
+    ]
+}
+
+/// Cubic spline sampled `samples` times between each pair of points, with the line extended straight past
+/// either end so that both curves start and finish on the end points
+fn spline(points: &[Point], samples: usize, weights: fn(f64) -> [f64; 4]) -> Vec<(Point, f64)> {
+    let last = points.len() - 1;
+    let control = |index: isize| -> (Point, f64) {
+        let reflect = |(ax, ay): Point, (bx, by): Point| (2.0 * ax - bx, 2.0 * ay - by);
+        let point = match index {
+            -1 => reflect(points[0], points[1]),
+            i if i as usize > last => reflect(points[last], points[last - 1]),
+            i => points[i as usize],
+        };
+        (point, index as f64)
+    };
+    let samples = samples.max(1);
+    let mut out = vec![];
+    for segment in 0..last as isize {
+        let controls = [control(segment - 1), control(segment), control(segment + 1), control(segment + 2)];
+        for sample in 0..samples {
+            let u = sample as f64 / samples as f64;
+            let (point, _) = mix(&weights(u), &controls);
+            out.push((point, segment as f64 + u));
+        }
+    }
+    out[0] = (points[0], 0.0);
+    out.push((points[last], last as f64));
+    out
+}
+
+/// Each point moved to the mean of the points within half of `length` metres along the path either side of it,
+/// narrowing the window towards the ends so that they stay in place
+fn window(points: &[Point], length: f64) -> Vec<(Point, f64)> {
+    let mut along = vec![0.0];
+    for pair in points.windows(2) {
+        let step = geo::Point::from(pair[0]).haversine_distance(&geo::Point::from(pair[1]));
+        along.push(along[along.len() - 1] + step);
+    }
+    let total = along[along.len() - 1];
+    // the window only ever moves forward along the path, so its ends and running sum are carried between points
+    let (mut start, mut end) = (0, 0);
+    let mut sum = (0.0, 0.0);
+    (0..points.len())
+        .map(|index| {
+            let half = (length / 2.0).min(along[index]).min(total - along[index]);
+            while end < points.len() && along[end] - along[index] <= half {
+                sum = (sum.0 + points[end].0, sum.1 + points[end].1);
+                end += 1;
+            }
+            while along[index] - along[start] > half {
+                sum = (sum.0 - points[start].0, sum.1 - points[start].1);
+                start += 1;
+            }
+            // a lone point, as at either end, is taken as it is rather than from the sum, which can carry rounding
+            let count = (end - start) as f64;
+            let point = if end - start == 1 { points[start] } else { (sum.0 / count, sum.1 / count) };
+            (point, index as f64)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // saw-tooth running east along the equator, 0.001 degrees either side of it
+    fn zigzag() -> Vec<Point> {
+        (0..=20).map(|x| (x as f64 * 0.001, if x % 2 == 0 { 0.001 } else { -0.001 })).collect()
+    }
+
+    fn largest_offset(line: &[(Point, f64)]) -> f64 {
+        line[1..line.len() - 1].iter().map(|((_, y), _)| y.abs()).fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn smoothing_keeps_the_ends_and_flattens_the_teeth() {
+        let points = zigzag();
+        for smoothing in [
+            Smoothing::Chaikin(3),
+            Smoothing::CatmullRom(4),
+            Smoothing::BSpline(4),
+            Smoothing::Window(500.0),
+        ] {
+            let line = smooth(&points, smoothing);
+            assert_eq!(line.first(), Some(&(points[0], 0.0)), "{}", smoothing);
+            assert_eq!(line.last(), Some(&(points[20], 20.0)), "{}", smoothing);
+            assert!(line.windows(2).all(|pair| pair[0].1 <= pair[1].1), "{}", smoothing);
+            if smoothing != Smoothing::CatmullRom(4) {
+                // away from the ends the line is well inside the teeth
+                let middle: Vec<_> = line.iter().filter(|(_, position)| (4.0..=16.0).contains(position)).copied().collect();
+                assert!(largest_offset(&middle) < 0.0006, "{}", smoothing);
+            }
+        }
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_point() {
+        let points = zigzag();
+        let line = smooth(&points, Smoothing::CatmullRom(3));
+        assert_eq!(line.len(), 3 * 20 + 1);
+        for (index, &point) in points.iter().enumerate() {
+            let (at, _) = line[3 * index];
+            assert!((at.0 - point.0).abs() < 1e-12 && (at.1 - point.1).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn parse_smoothing_names() {
+        assert_eq!("chaikin".parse(), Ok(Smoothing::Chaikin(3)));
+        assert_eq!("Catmull-Rom:8".parse(), Ok(Smoothing::CatmullRom(8)));
+        assert_eq!("bspline".parse(), Ok(Smoothing::BSpline(4)));
+        assert_eq!("window:250".parse(), Ok(Smoothing::Window(250.0)));
+        assert_eq!(Smoothing::Window(250.0).to_string().parse(), Ok(Smoothing::Window(250.0)));
+        assert!("chaikin:1.5".parse::<Smoothing>().is_err());
+        assert!("chaikin:64".parse::<Smoothing>().is_err());
+        assert_eq!("catmull-rom:1024".parse(), Ok(Smoothing::CatmullRom(1024)));
+        assert!("catmull-rom:1025".parse::<Smoothing>().is_err());
+        assert!("bspline:1e12".parse::<Smoothing>().is_err());
+        assert!("window:0".parse::<Smoothing>().is_err());
+        assert!("window:nan".parse::<Smoothing>().is_err());
+        assert!("window:inf".parse::<Smoothing>().is_err());
+        assert!("gaussian".parse::<Smoothing>().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use thalweg::read;
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::Command;
+
+// fresh directory for the files of one test
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("thalweg-cli-{}", name));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("data")).unwrap();
+    directory
+}
+
+#[test]
+fn centre_method_stays_on_the_centreline() {
+    let directory = directory("centre");
+    // deepest soundings zigzag either side of the middle of a narrow channel, within reach of sinking onto them
+    let mut data = "latitude,longitude,depth\n".to_string();
+    for x in 0..=30 {
+        for y in 0..=6 {
+            let depth = if y == [2, 4][x % 2] { 50.0 } else { 10.0 };
+            data += &format!("{},{},{}\n", y as f64 * 0.0003, x as f64 * 0.00075, depth);
+        }
+    }
+    fs::write(directory.join("data").join("channel.csv"), data).unwrap();
+    fs::write(directory.join("points.csv"), "latitude,longitude\n0.0009,0.0\n0.0009,0.0225\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_thalweg"))
+        .args(["generate", "--method", "centre", "--sparse", "--no-cache", "-r", "150", "-f", "csv", "-p"])
+        .arg(&directory)
+        .arg(directory.join("points.csv"))
+        .arg(directory.join("data"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut reader = BufReader::new(File::open(directory.join("path.csv")).unwrap());
+    let path = read::thalweg::from_csv(&mut reader).unwrap();
+    assert_eq!(path.len(), 31);
+    assert!(path.iter().all(|point| point.point().1 == 0.0009), "{:?}", path);
+}
+
+#[test]
+fn optional_positions_must_lie_between_the_ends() {
+    let directory = directory("optional");
+    fs::write(directory.join("data").join("soundings.csv"), "latitude,longitude,depth\n0.0,0.0,10.0\n").unwrap();
+    fs::write(directory.join("points.csv"), "latitude,longitude\n0.0,0.0\n0.0,0.01\n0.0,0.02\n").unwrap();
+
+    for position in ["0", "1", "3", "4"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_thalweg"))
+            .args(["generate", "--no-cache", "--optional", position, "-p"])
+            .arg(&directory)
+            .arg(directory.join("points.csv"))
+            .arg(directory.join("data"))
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", position);
+        let error = String::from_utf8_lossy(&output.stderr);
+        assert!(error.contains(&format!("--optional {} ", position)), "{}", error);
+    }
+}
+
+#[test]
+fn extents_are_recorded_without_the_cache() {
+    let directory = directory("extents");
+    let mut data = "latitude,longitude,depth\n".to_string();
+    for x in 0..=10 {
+        data += &format!("0.0,{},10.0\n", x as f64 * 0.001);
+    }
+    fs::write(directory.join("data").join("soundings.csv"), data).unwrap();
+    fs::write(directory.join("points.csv"), "latitude,longitude\n0.0,0.0\n0.0,0.01\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_thalweg"))
+        .args(["generate", "--no-cache", "-p"])
+        .arg(&directory)
+        .arg(directory.join("points.csv"))
+        .arg(directory.join("data"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(directory.join("data").join(".thwextents").exists());
+    assert!(!directory.join("data").join(".thwcache").exists());
+}
+
+#[test]
+fn malformed_land_is_reported_with_its_file() {
+    let directory = directory("land");
+    fs::write(directory.join("data").join("soundings.csv"), "latitude,longitude,depth\n0.0,0.0,10.0\n").unwrap();
+    fs::write(directory.join("points.csv"), "latitude,longitude\n0.0,0.0\n0.0,0.01\n").unwrap();
+    fs::write(directory.join("land.wkt"), "POLYGON ((0 0, 1 x, 0 0))").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_thalweg"))
+        .args(["generate", "--no-cache", "--land"])
+        .arg(directory.join("land.wkt"))
+        .arg("-p")
+        .arg(&directory)
+        .arg(directory.join("points.csv"))
+        .arg(directory.join("data"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("land.wkt") && error.contains("Malformed WKT"), "{}", error);
+}